@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+use cloth_simulation_bevy_rust::simulation::{Params, Simulation};
+
+/// Parameters of the demo for a cloth of the given size
+pub fn params(num_nodes_x: usize, num_nodes_y: usize) -> Params {
+    Params {
+        num_nodes_x,
+        num_nodes_y,
+        dt: 0.025,
+        m: 1.0,
+        g: 1000.0,
+        gravity_direction: Vec3::new(0.0, -1.0, 0.0),
+        r: Vec3::new(20.0, 0.0, 0.0),
+        k: Vec3::new(3600.0, 1.0, 1.0),
+        dampen_factor: 0.99,
+        air_density: 0.00001,
+        drag_coefficient: 1.0,
+        lift_coefficient: 0.5,
+        ..Default::default()
+    }
+}
+
+/// Headless world with the cloth spawned
+pub fn world(params: Params) -> World {
+    let mut world = World::new();
+    Simulation::new(params).setup_world(&mut world);
+    world
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use bevy::prelude::*;
+use cloth_simulation_bevy_rust::simulation::{Edge, Params, Simulation, SpringSolver, WindModel};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const SIZES: [(usize, usize); 3] = [(50, 30), (100, 100), (250, 250)];
+
+/// Runs `physics_update` and the systems around it on every grid size
+fn bench_physics_update(c: &mut Criterion, name: &str, setup: impl Fn(Params) -> World) {
+    let mut group = c.benchmark_group(name);
+    group.sample_size(20);
+
+    for (x, y) in SIZES {
+        let mut world = setup(common::params(x, y));
+        // Only the app clears the removals at the end of a frame, left alone they would count
+        // as new cuts in every update
+        world.clear_trackers();
+        let mut stage = Simulation::physics_stage();
+
+        group.bench_function(BenchmarkId::from_parameter(format!("{}x{}", x, y)), |b| {
+            b.iter(|| {
+                stage.run(&mut world);
+                world.clear_trackers();
+            })
+        });
+    }
+
+    group.finish();
+}
+
+/// Integration under gravity only, all edges are removed
+fn gravity(c: &mut Criterion) {
+    bench_physics_update(c, "gravity", |params| {
+        let mut world = common::world(params);
+
+        let edges: Vec<Entity> = world
+            .query_filtered::<Entity, With<Edge>>()
+            .iter(&world)
+            .collect();
+        for edge in edges {
+            world.despawn(edge);
+        }
+
+        world
+    });
+}
+
+/// Gravity together with spring solving
+fn springs(c: &mut Criterion) {
+    bench_physics_update(c, "springs", common::world);
+}
+
+/// Springs solved one edge after the other, to compare with the parallel tasks of `springs`
+fn springs_serial(c: &mut Criterion) {
+    bench_physics_update(c, "springs_serial", |params| {
+        common::world(Params {
+            spring_solver: SpringSolver::Serial,
+            ..params
+        })
+    });
+}
+
+fn wind_push(c: &mut Criterion) {
+    bench_physics_update(c, "wind_push", |params| {
+        common::world(Params {
+            enable_wind: true,
+            wind_model: WindModel::Push,
+            ..params
+        })
+    });
+}
+
+fn wind_aerodynamic(c: &mut Criterion) {
+    bench_physics_update(c, "wind_aerodynamic", |params| {
+        common::world(Params {
+            enable_wind: true,
+            wind_model: WindModel::Aerodynamic,
+            ..params
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    gravity,
+    springs,
+    springs_serial,
+    wind_push,
+    wind_aerodynamic
+);
+criterion_main!(benches);
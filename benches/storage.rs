@@ -0,0 +1,47 @@
+mod common;
+
+use bevy::prelude::*;
+use cloth_simulation_bevy_rust::simulation::{Params, Simulation, SpringSolver, StorageBackend};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+/// Compares a physics update on the node entities with the contiguous storage. The contiguous
+/// storage solves the springs serially, so the entities do too, the parallel entity solver
+/// is measured on its own for reference.
+fn storage_backends(c: &mut Criterion) {
+    let mut group = c.benchmark_group("storage_backend");
+
+    let cases = [
+        ("EcsSerial", StorageBackend::Ecs, SpringSolver::Serial),
+        (
+            "Contiguous",
+            StorageBackend::Contiguous,
+            SpringSolver::Serial,
+        ),
+        ("EcsParallel", StorageBackend::Ecs, SpringSolver::Parallel),
+    ];
+
+    for size in [50, 100, 200] {
+        for (name, storage, spring_solver) in cases {
+            let mut world = common::world(Params {
+                storage,
+                spring_solver,
+                ..common::params(size, size)
+            });
+            world.clear_trackers();
+            let mut stage = Simulation::physics_stage();
+
+            group.bench_function(BenchmarkId::new(name, format!("{}x{}", size, size)), |b| {
+                b.iter(|| {
+                    stage.run(&mut world);
+                    // Cleared like at the end of every frame of the app
+                    world.clear_trackers();
+                })
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, storage_backends);
+criterion_main!(benches);
@@ -0,0 +1,703 @@
+use std::fs;
+
+use crate::simulation::{
+    AdaptiveSteps, DampingModel, DampingOptions, DetachedAction, ExplosionResponse, ExportFormat,
+    Params, SpringSolver, StabilityOptions, StorageBackend, WindModel, WorldBounds,
+};
+use bevy::prelude::*;
+use bevy::sprite::Rect;
+
+pub const USAGE: &str = "\
+Usage: cloth-simulation-bevy-rust [OPTIONS] [SHAPE]
+
+SHAPE is an optional OBJ mesh or PNG mask giving the shape of the cloth.
+
+Options:
+  --config <PATH>    read options from a file with one `key = value` per line
+  --<KEY> <VALUE>    set any parameter, e.g. --dt 0.02 or --bounds.floor -1000
+  --help             print this message
+
+Keys are the names of the parameter fields, nested ones joined by a dot
+(damping.model, stability.response, ...), plus window.width and window.height.
+Vectors are written as x,y,z, rectangles as min_x,min_y,max_x,max_y and
+optional values as none.";
+
+/// Everything the binary can be configured with
+#[derive(Clone)]
+pub struct Config {
+    pub params: Params,
+    pub window_width: f32,
+    pub window_height: f32,
+    /// path of an OBJ mesh or PNG mask
+    pub shape: Option<String>,
+    /// `--help` was given, the usage should be printed instead of running
+    pub help: bool,
+}
+
+impl Default for Config {
+    /// The demo scenario, the optional features are off until enabled by config or the UI
+    fn default() -> Self {
+        Config {
+            params: Params {
+                node_size: 10.0,
+                num_nodes_x: 50,
+                num_nodes_y: 30,
+                dt: 0.025,
+                m: 1.0,
+                g: 1000.0,
+                gravity_direction: Vec3::new(0.0, -1.0, 0.0),
+                bounds: WorldBounds {
+                    floor: None,
+                    left_wall: None,
+                    right_wall: None,
+                    restitution: 0.3,
+                    friction: 0.5,
+                },
+                mouse_force: Vec3::new(11000.0, 5000.0, 0.0),
+                r: Vec3::new(20.0, 0.0, 0.0),
+                k: Vec3::new(3600.0, 1.0, 1.0),
+                enable_tethers: false,
+                bending_stiffness: 0.0,
+                dampen_factor: 0.99,
+                damping: DampingOptions {
+                    model: DampingModel::Multiplicative,
+                    air_drag: 2.0,
+                    spring_damping: 20.0,
+                    rayleigh_alpha: 1.0,
+                    rayleigh_beta: 0.005,
+                },
+                enable_wind: false,
+                wind_model: WindModel::Push,
+                air_density: 0.00001,
+                drag_coefficient: 1.0,
+                lift_coefficient: 0.5,
+                through_wind: 500.0,
+                stability: StabilityOptions {
+                    enabled: false,
+                    max_velocity: 20000.0,
+                    response: ExplosionResponse::Rollback,
+                },
+                adaptive_steps: AdaptiveSteps {
+                    enabled: false,
+                    min_steps: 3,
+                    max_steps: 20,
+                    max_strain: 0.2,
+                    max_velocity: 5000.0,
+                },
+                side_panel_width: 300.0,
+                ..Default::default()
+            },
+            window_width: 1280.0,
+            window_height: 720.0,
+            shape: None,
+            help: false,
+        }
+    }
+}
+
+impl Config {
+    /// Applies the config file given with `--config` first and the other flags on top of
+    /// it. For `--help` the rest is skipped and only `help` is set.
+    pub fn from_args(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut flags: Vec<(String, String)> = Vec::new();
+        let mut shape = None;
+
+        let mut args = args.peekable();
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Ok(Config {
+                    help: true,
+                    ..Default::default()
+                });
+            }
+
+            match arg.strip_prefix("--") {
+                Some(flag) => {
+                    let (key, value) = match flag.split_once('=') {
+                        Some((key, value)) => (key.to_string(), value.to_string()),
+                        None => {
+                            let value = args
+                                .next()
+                                .ok_or_else(|| format!("missing value for --{}", flag))?;
+                            (flag.to_string(), value)
+                        }
+                    };
+                    flags.push((key, value));
+                }
+                None if shape.is_none() => shape = Some(arg),
+                None => return Err(format!("unexpected argument {}, see --help", arg)),
+            }
+        }
+
+        let mut config = Config {
+            shape,
+            ..Default::default()
+        };
+
+        for (_, path) in flags.iter().filter(|(key, _)| key == "config") {
+            config.apply_file(path)?;
+        }
+        for (key, value) in flags.iter().filter(|(key, _)| key != "config") {
+            config.set(key, value)?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads a URL query string like `?dt=0.02&bounds.floor=-1000`, every key except `shape`
+    pub fn from_query(query: &str) -> Result<Self, String> {
+        let mut config = Config::default();
+
+        for pair in query.trim_start_matches('?').split('&') {
+            if pair.is_empty() {
+                continue;
+            }
+
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let key = decode(key);
+            // The page has no file system to load a mask or mesh from
+            if key == "shape" {
+                return Err("shape can not be set in the URL".to_string());
+            }
+            config.set(&key, &decode(value))?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reads the overrides from the query string of the page, invalid ones are reported on
+    /// the console and the demo scenario is used instead
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_url() -> Self {
+        let query = web_sys::window()
+            .and_then(|window| window.location().search().ok())
+            .unwrap_or_default();
+
+        Self::from_query(&query).unwrap_or_else(|err| {
+            web_sys::console::warn_1(&format!("ignoring URL parameters: {}", err).into());
+            Config::default()
+        })
+    }
+
+    /// Reads `key = value` lines, empty lines and lines starting with # are skipped
+    pub fn apply_file(&mut self, path: &str) -> Result<(), String> {
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("could not read config {}: {}", path, err))?;
+
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("{}:{}: expected key = value", path, number + 1))?;
+            self.set(key.trim(), value.trim())
+                .map_err(|err| format!("{}:{}: {}", path, number + 1, err))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "shape" => self.shape = Some(value.to_string()),
+            "window.width" => self.window_width = parse(key, value)?,
+            "window.height" => self.window_height = parse(key, value)?,
+            _ => set_param(&mut self.params, key, value)?,
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        check(
+            self.window_width > 0.0 && self.window_height > 0.0,
+            "window size must be positive",
+        )?;
+        validate_params(&self.params)
+    }
+}
+
+const DETACHED_ACTIONS: [(&str, DetachedAction); 3] = [
+    ("simulate", DetachedAction::Simulate),
+    ("sleep", DetachedAction::Sleep),
+    ("despawn", DetachedAction::Despawn),
+];
+const STORAGE_BACKENDS: [(&str, StorageBackend); 2] = [
+    ("ecs", StorageBackend::Ecs),
+    ("contiguous", StorageBackend::Contiguous),
+];
+const SPRING_SOLVERS: [(&str, SpringSolver); 2] = [
+    ("parallel", SpringSolver::Parallel),
+    ("serial", SpringSolver::Serial),
+];
+const EXPLOSION_RESPONSES: [(&str, ExplosionResponse); 3] = [
+    ("pause", ExplosionResponse::Pause),
+    ("clamp", ExplosionResponse::ClampVelocities),
+    ("rollback", ExplosionResponse::Rollback),
+];
+const EXPORT_FORMATS: [(&str, ExportFormat); 2] =
+    [("obj", ExportFormat::Obj), ("gltf", ExportFormat::Gltf)];
+const DAMPING_MODELS: [(&str, DampingModel); 4] = [
+    ("multiplicative", DampingModel::Multiplicative),
+    ("air_drag", DampingModel::AirDrag),
+    ("spring_axis", DampingModel::SpringAxis),
+    ("rayleigh", DampingModel::Rayleigh),
+];
+const WIND_MODELS: [(&str, WindModel); 2] = [
+    ("push", WindModel::Push),
+    ("aerodynamic", WindModel::Aerodynamic),
+];
+
+/// Parameters only read when the app starts, changing them later has no effect
+pub const STARTUP_PARAMS: [&str; 5] = ["num_nodes_x", "num_nodes_y", "dt", "m", "side_panel_width"];
+
+/// Sets a single parameter by its field name, nested fields are joined by a dot
+pub fn set_param(params: &mut Params, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "node_size" => params.node_size = parse(key, value)?,
+        "num_nodes_x" => params.num_nodes_x = parse(key, value)?,
+        "num_nodes_y" => params.num_nodes_y = parse(key, value)?,
+        "dt" => params.dt = parse(key, value)?,
+        "m" => params.m = parse(key, value)?,
+        "g" => params.g = parse(key, value)?,
+        "gravity_direction" => params.gravity_direction = parse_vec3(key, value)?,
+        "bounds.floor" => params.bounds.floor = parse_optional(key, value)?,
+        "bounds.left_wall" => params.bounds.left_wall = parse_optional(key, value)?,
+        "bounds.right_wall" => params.bounds.right_wall = parse_optional(key, value)?,
+        "bounds.restitution" => params.bounds.restitution = parse(key, value)?,
+        "bounds.friction" => params.bounds.friction = parse(key, value)?,
+        "fragments.color" => params.fragments.color = parse(key, value)?,
+        "fragments.detached_action" => {
+            params.fragments.detached_action = parse_name(key, value, &DETACHED_ACTIONS)?
+        }
+        "storage" => params.storage = parse_name(key, value, &STORAGE_BACKENDS)?,
+        "spring_solver" => params.spring_solver = parse_name(key, value, &SPRING_SOLVERS)?,
+        "stability.enabled" => params.stability.enabled = parse(key, value)?,
+        "stability.max_velocity" => params.stability.max_velocity = parse(key, value)?,
+        "stability.response" => {
+            params.stability.response = parse_name(key, value, &EXPLOSION_RESPONSES)?
+        }
+        "export.format" => params.export.format = parse_name(key, value, &EXPORT_FORMATS)?,
+        "export.interval" => params.export.interval = parse(key, value)?,
+        "export.svg_strain_colors" => params.export.svg_strain_colors = parse(key, value)?,
+        "export.svg_nodes" => params.export.svg_nodes = parse(key, value)?,
+        "record.enabled" => params.record.enabled = parse(key, value)?,
+        "record.width" => params.record.width = parse(key, value)?,
+        "record.height" => params.record.height = parse(key, value)?,
+        "record.node_radius" => params.record.node_radius = parse(key, value)?,
+        "adaptive_steps.enabled" => params.adaptive_steps.enabled = parse(key, value)?,
+        "adaptive_steps.min_steps" => params.adaptive_steps.min_steps = parse(key, value)?,
+        "adaptive_steps.max_steps" => params.adaptive_steps.max_steps = parse(key, value)?,
+        "adaptive_steps.max_strain" => params.adaptive_steps.max_strain = parse(key, value)?,
+        "adaptive_steps.max_velocity" => params.adaptive_steps.max_velocity = parse(key, value)?,
+        "paused" => params.paused = parse(key, value)?,
+        "mouse_force" => params.mouse_force = parse_vec3(key, value)?,
+        "r" => params.r = parse_vec3(key, value)?,
+        "k" => params.k = parse_vec3(key, value)?,
+        "enable_tethers" => params.enable_tethers = parse(key, value)?,
+        "bending_stiffness" => params.bending_stiffness = parse(key, value)?,
+        "dampen_factor" => params.dampen_factor = parse(key, value)?,
+        "damping.model" => params.damping.model = parse_name(key, value, &DAMPING_MODELS)?,
+        "damping.air_drag" => params.damping.air_drag = parse(key, value)?,
+        "damping.spring_damping" => params.damping.spring_damping = parse(key, value)?,
+        "damping.rayleigh_alpha" => params.damping.rayleigh_alpha = parse(key, value)?,
+        "damping.rayleigh_beta" => params.damping.rayleigh_beta = parse(key, value)?,
+        "enable_wind" => params.enable_wind = parse(key, value)?,
+        "wind_model" => params.wind_model = parse_name(key, value, &WIND_MODELS)?,
+        "air_density" => params.air_density = parse(key, value)?,
+        "drag_coefficient" => params.drag_coefficient = parse(key, value)?,
+        "lift_coefficient" => params.lift_coefficient = parse(key, value)?,
+        "through_wind" => params.through_wind = parse(key, value)?,
+        "wind_domain" => {
+            params.wind_domain = match value {
+                "none" => None,
+                _ => {
+                    let [min_x, min_y, max_x, max_y] = parse_list(key, value)?;
+                    Some(Rect {
+                        min: Vec2::new(min_x, min_y),
+                        max: Vec2::new(max_x, max_y),
+                    })
+                }
+            }
+        }
+        "side_panel_width" => params.side_panel_width = parse(key, value)?,
+        _ => return Err(format!("unknown parameter {}", key)),
+    }
+
+    Ok(())
+}
+
+/// Every parameter with its value, written the way `set_param` reads it
+pub fn param_values(params: &Params) -> Vec<(&'static str, String)> {
+    let optional = |value: Option<f32>| value.map_or("none".to_string(), |value| value.to_string());
+    let vec3 = |value: Vec3| format!("{},{},{}", value.x, value.y, value.z);
+
+    vec![
+        ("node_size", params.node_size.to_string()),
+        ("num_nodes_x", params.num_nodes_x.to_string()),
+        ("num_nodes_y", params.num_nodes_y.to_string()),
+        ("dt", params.dt.to_string()),
+        ("m", params.m.to_string()),
+        ("g", params.g.to_string()),
+        ("gravity_direction", vec3(params.gravity_direction)),
+        ("bounds.floor", optional(params.bounds.floor)),
+        ("bounds.left_wall", optional(params.bounds.left_wall)),
+        ("bounds.right_wall", optional(params.bounds.right_wall)),
+        ("bounds.restitution", params.bounds.restitution.to_string()),
+        ("bounds.friction", params.bounds.friction.to_string()),
+        ("fragments.color", params.fragments.color.to_string()),
+        (
+            "fragments.detached_action",
+            name_of(params.fragments.detached_action, &DETACHED_ACTIONS),
+        ),
+        ("storage", name_of(params.storage, &STORAGE_BACKENDS)),
+        (
+            "spring_solver",
+            name_of(params.spring_solver, &SPRING_SOLVERS),
+        ),
+        ("stability.enabled", params.stability.enabled.to_string()),
+        (
+            "stability.max_velocity",
+            params.stability.max_velocity.to_string(),
+        ),
+        (
+            "stability.response",
+            name_of(params.stability.response, &EXPLOSION_RESPONSES),
+        ),
+        (
+            "export.format",
+            name_of(params.export.format, &EXPORT_FORMATS),
+        ),
+        ("export.interval", params.export.interval.to_string()),
+        (
+            "export.svg_strain_colors",
+            params.export.svg_strain_colors.to_string(),
+        ),
+        ("export.svg_nodes", params.export.svg_nodes.to_string()),
+        ("record.enabled", params.record.enabled.to_string()),
+        ("record.width", params.record.width.to_string()),
+        ("record.height", params.record.height.to_string()),
+        ("record.node_radius", params.record.node_radius.to_string()),
+        (
+            "adaptive_steps.enabled",
+            params.adaptive_steps.enabled.to_string(),
+        ),
+        (
+            "adaptive_steps.min_steps",
+            params.adaptive_steps.min_steps.to_string(),
+        ),
+        (
+            "adaptive_steps.max_steps",
+            params.adaptive_steps.max_steps.to_string(),
+        ),
+        (
+            "adaptive_steps.max_strain",
+            params.adaptive_steps.max_strain.to_string(),
+        ),
+        (
+            "adaptive_steps.max_velocity",
+            params.adaptive_steps.max_velocity.to_string(),
+        ),
+        ("paused", params.paused.to_string()),
+        ("mouse_force", vec3(params.mouse_force)),
+        ("r", vec3(params.r)),
+        ("k", vec3(params.k)),
+        ("enable_tethers", params.enable_tethers.to_string()),
+        ("bending_stiffness", params.bending_stiffness.to_string()),
+        ("dampen_factor", params.dampen_factor.to_string()),
+        (
+            "damping.model",
+            name_of(params.damping.model, &DAMPING_MODELS),
+        ),
+        ("damping.air_drag", params.damping.air_drag.to_string()),
+        (
+            "damping.spring_damping",
+            params.damping.spring_damping.to_string(),
+        ),
+        (
+            "damping.rayleigh_alpha",
+            params.damping.rayleigh_alpha.to_string(),
+        ),
+        (
+            "damping.rayleigh_beta",
+            params.damping.rayleigh_beta.to_string(),
+        ),
+        ("enable_wind", params.enable_wind.to_string()),
+        ("wind_model", name_of(params.wind_model, &WIND_MODELS)),
+        ("air_density", params.air_density.to_string()),
+        ("drag_coefficient", params.drag_coefficient.to_string()),
+        ("lift_coefficient", params.lift_coefficient.to_string()),
+        ("through_wind", params.through_wind.to_string()),
+        (
+            "wind_domain",
+            params.wind_domain.map_or("none".to_string(), |domain| {
+                format!(
+                    "{},{},{},{}",
+                    domain.min.x, domain.min.y, domain.max.x, domain.max.y
+                )
+            }),
+        ),
+        ("side_panel_width", params.side_panel_width.to_string()),
+    ]
+}
+
+/// Query string of the parameters which differ from the demo scenario, empty if none do
+pub fn query_string(params: &Params) -> String {
+    let defaults = param_values(&Config::default().params);
+
+    param_values(params)
+        .into_iter()
+        .zip(defaults)
+        .filter(|(value, default)| value != default)
+        .map(|((key, value), _)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Rejects parameters the simulation can not run with
+pub fn validate_params(params: &Params) -> Result<(), String> {
+    let non_negative = |value: f32| value.is_finite() && value >= 0.0;
+    let fraction = |value: f32| (0.0..=1.0).contains(&value);
+
+    check(
+        params.num_nodes_x > 0 && params.num_nodes_y > 0,
+        "the cloth needs at least one node in each direction",
+    )?;
+    check(
+        params.dt.is_finite() && params.dt > 0.0,
+        "dt must be positive",
+    )?;
+    check(
+        params.m.is_finite() && params.m > 0.0,
+        "mass must be positive",
+    )?;
+    check(params.g.is_finite(), "gravity must be finite")?;
+    check(
+        params.through_wind.is_finite(),
+        "through wind must be finite",
+    )?;
+    check(
+        params.gravity_direction.is_finite(),
+        "gravity direction must be finite",
+    )?;
+    check(
+        params.r[0].is_finite() && params.r[0] > 0.0,
+        "structural rest length must be positive",
+    )?;
+    check(
+        non_negative(params.k[0]),
+        "structural spring coefficient must not be negative",
+    )?;
+    check(
+        non_negative(params.node_size),
+        "node size must not be negative",
+    )?;
+    check(
+        fraction(params.bounds.restitution) && fraction(params.bounds.friction),
+        "restitution and friction must be between 0 and 1",
+    )?;
+    if let (Some(left_wall), Some(right_wall)) = (params.bounds.left_wall, params.bounds.right_wall)
+    {
+        check(
+            left_wall < right_wall,
+            "left wall must be left of the right wall",
+        )?;
+    }
+    check(
+        fraction(params.bending_stiffness),
+        "bending stiffness must be between 0 and 1",
+    )?;
+    check(
+        fraction(params.dampen_factor),
+        "dampen factor must be between 0 and 1",
+    )?;
+    check(
+        [
+            params.damping.air_drag,
+            params.damping.spring_damping,
+            params.damping.rayleigh_alpha,
+            params.damping.rayleigh_beta,
+        ]
+        .into_iter()
+        .all(non_negative),
+        "damping coefficients must not be negative",
+    )?;
+    check(
+        non_negative(params.air_density)
+            && non_negative(params.drag_coefficient)
+            && non_negative(params.lift_coefficient),
+        "air density, drag and lift coefficients must not be negative",
+    )?;
+    if let Some(domain) = params.wind_domain {
+        check(
+            domain.min.x < domain.max.x && domain.min.y < domain.max.y,
+            "wind domain min must be below its max",
+        )?;
+    }
+    check(
+        params.stability.max_velocity > 0.0,
+        "stability max velocity must be positive",
+    )?;
+    if params.adaptive_steps.enabled {
+        let steps = params.adaptive_steps;
+        check(
+            steps.min_steps > 0 && steps.min_steps <= steps.max_steps,
+            "adaptive steps need 0 < min_steps <= max_steps",
+        )?;
+        check(
+            steps.max_strain > 0.0 && steps.max_velocity > 0.0,
+            "adaptive steps bounds must be positive",
+        )?;
+    }
+    if params.record.enabled {
+        // The frames are written to files
+        check(
+            !cfg!(target_arch = "wasm32"),
+            "recording is not available on web",
+        )?;
+        check(
+            params.record.width > 0 && params.record.height > 0,
+            "record size must be positive",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Percent decoding of a query string component, `+` is a space
+fn decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn check(condition: bool, message: &str) -> Result<(), String> {
+    if condition {
+        Ok(())
+    } else {
+        Err(message.to_string())
+    }
+}
+
+fn invalid(key: &str, value: &str, expected: &str) -> String {
+    format!("invalid value {} for {}, expected {}", value, key, expected)
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid value {} for {}", value, key))
+}
+
+fn parse_name<T: Copy>(key: &str, value: &str, names: &[(&str, T)]) -> Result<T, String> {
+    names
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, item)| *item)
+        .ok_or_else(|| {
+            let expected: Vec<&str> = names.iter().map(|(name, _)| *name).collect();
+            invalid(key, value, &expected.join(", "))
+        })
+}
+
+fn name_of<T: PartialEq>(item: T, names: &[(&str, T)]) -> String {
+    names
+        .iter()
+        .find(|(_, other)| *other == item)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_default()
+}
+
+fn parse_optional(key: &str, value: &str) -> Result<Option<f32>, String> {
+    match value {
+        "none" => Ok(None),
+        _ => parse(key, value).map(Some),
+    }
+}
+
+fn parse_list<const N: usize>(key: &str, value: &str) -> Result<[f32; N], String> {
+    let values = value
+        .split(',')
+        .map(|value| parse(key, value))
+        .collect::<Result<Vec<f32>, String>>()?;
+
+    values
+        .try_into()
+        .map_err(|_| format!("{} needs {} comma separated values", key, N))
+}
+
+fn parse_vec3(key: &str, value: &str) -> Result<Vec3, String> {
+    parse_list(key, value).map(Vec3::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::DampingModel;
+
+    #[test]
+    fn config_flags_override_defaults() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let config = Config::from_args(
+            args(&["--num_nodes_x", "8", "--bounds.floor=-800", "--r", "5,0,0"]).into_iter(),
+        )
+        .unwrap();
+        assert_eq!(config.params.num_nodes_x, 8);
+        assert_eq!(config.params.bounds.floor, Some(-800.0));
+        assert_eq!(config.params.r, Vec3::new(5.0, 0.0, 0.0));
+
+        assert!(Config::from_args(args(&["--num_nodes_y", "0"]).into_iter()).is_err());
+        assert!(Config::from_args(args(&["--m", "-1"]).into_iter()).is_err());
+        assert!(Config::from_args(args(&["--unknown", "1"]).into_iter()).is_err());
+
+        assert!(
+            Config::from_args(args(&["--num_nodes_x", "8", "--help"]).into_iter())
+                .unwrap()
+                .help
+        );
+    }
+
+    #[test]
+    fn shareable_query_round_trips() {
+        let mut params = Config::default().params;
+        params.dt = 0.02;
+        params.bounds.floor = Some(-800.0);
+        params.damping.model = DampingModel::Rayleigh;
+
+        let query = query_string(&params);
+        assert_eq!(
+            query,
+            "dt=0.02&bounds.floor=-800&damping.model=rayleigh".to_string()
+        );
+
+        let config = Config::from_query(&format!("?{}", query)).unwrap();
+        assert_eq!(param_values(&config.params), param_values(&params));
+        assert!(Config::from_query("?m=-1").is_err());
+        assert!(Config::from_query("?dt=0.02&shape=banner.png").is_err());
+    }
+}
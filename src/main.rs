@@ -1,32 +1,41 @@
-mod simulation;
-
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
-use simulation::{Params, Simulation};
+use cloth_simulation_bevy_rust::config::{Config, USAGE};
+use cloth_simulation_bevy_rust::simulation::{ClothMask, ObjMesh, Simulation};
 
 pub fn main() -> Result<(), String> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let config = Config::from_args(std::env::args().skip(1))?;
+    #[cfg(target_arch = "wasm32")]
+    let config = Config::from_url();
+
+    if config.help {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+
+    let mut simulation = Simulation::new(config.params);
+
+    // Optional OBJ mesh or PNG mask giving the shape of the cloth
+    if let Some(path) = config.shape {
+        simulation = if path.to_lowercase().ends_with(".obj") {
+            simulation.with_mesh(ObjMesh::load(&path)?)
+        } else {
+            simulation.with_mask(ClothMask::from_png(&path)?)
+        };
+    }
+
+    // The window plugin reads the descriptor when it is added
     App::new()
-        .add_plugins(DefaultPlugins)
-        .add_plugin(ShapePlugin)
-        .add_plugin(Simulation::new(Params {
-            node_size: 10.0,
-            num_nodes_x: 50,
-            num_nodes_y: 30,
-            dt: 0.025,
-            m: 1.0,
-            g: 1000.0,
-            mouse_force: Vec3::new(11000.0, 5000.0, 0.0),
-            r: Vec3::new(20.0, 0.0, 0.0),
-            k: Vec3::new(3600.0, 1.0, 1.0),
-            dampen_factor: 0.99,
-            enable_wind: false,
-            side_panel_width: 300.0,
-            ..Default::default()
-        }))
         .insert_resource(WindowDescriptor {
+            width: config.window_width,
+            height: config.window_height,
             fit_canvas_to_parent: true,
             ..default()
         })
+        .add_plugins(DefaultPlugins)
+        .add_plugin(ShapePlugin)
+        .add_plugin(simulation)
         .run();
 
     Ok(())
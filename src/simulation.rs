@@ -1,25 +1,117 @@
+mod aerodynamics;
+mod bending;
+mod bindings;
+mod bounds;
+mod damping;
+mod diagnostics;
+mod export;
+mod fragments;
+mod gestures;
+mod js_api;
+mod mask;
+mod mesh;
+mod obj;
 mod physics;
+mod recording;
+mod stability;
+mod storage;
+mod substeps;
+mod tethers;
+mod topology;
 mod ui;
 mod util;
+mod wind;
 
+use bending::{update_bending, Bending};
 use bevy::sprite::Rect;
+use bevy::tasks::{ComputeTaskPool, TaskPool};
 use bevy::{prelude::*, time::FixedTimestep};
 use bevy_egui::EguiPlugin;
 use bevy_prototype_debug_lines::*;
 use bevy_prototype_lyon::prelude::*;
-use physics::{
-    apply_wind, physics_update, Edge, Force, Index, Mass, Pinned, PreviousPosition, WindWave,
+pub use bindings::{Action, Binding, BindingInput, Bindings};
+pub use bounds::WorldBounds;
+pub use damping::{DampingModel, DampingOptions};
+use diagnostics::{record_energy, EnergyHistory};
+use export::{export_frames, export_svg, Exporter, Uv};
+pub use export::{ExportFormat, ExportOptions};
+use fragments::{handle_detached_fragments, update_fragments, Asleep, Fragments};
+pub use fragments::{DetachedAction, FragmentOptions};
+#[cfg(target_arch = "wasm32")]
+use js_api::{handle_js_commands, publish_node_positions};
+pub use mask::{Cell, ClothMask};
+use mesh::Faces;
+pub use obj::ObjMesh;
+use physics::{color_edges, physics_update, EdgeColoring};
+pub use physics::{Edge, Force, Index, Mass, Pinned, PreviousPosition, SpringSolver};
+pub use recording::RecordOptions;
+use recording::{record_frames, Recorder};
+use stability::{check_stability, Snapshot, StabilityWarning};
+pub use stability::{ExplosionResponse, StabilityOptions};
+pub use storage::StorageBackend;
+use storage::{physics_update_contiguous, ClothStorage};
+use substeps::adapt_substeps;
+pub use substeps::{AdaptiveSteps, Substeps};
+use tethers::update_tethers;
+pub use tethers::Tethers;
+use topology::{track_topology, Topology};
+use ui::{
+    handle_mouse_interaction, handle_shortcuts, run_if_wind_enabled, ui_side_panel, MainCamera,
 };
-use ui::{handle_mouse_interaction, run_if_wind_enabled, ui_side_panel, MainCamera};
+use wind::apply_wind;
+pub use wind::{WindModel, WindSource};
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
 struct FixedUpdateStage;
 
-/// Array containing all nodes, addressable by inded
-pub struct Grid(Vec<Vec<Entity>>);
+/// Array containing all nodes, addressable by inded. Cells outside of the cloth mask are empty.
+pub struct Grid(Vec<Vec<Option<Entity>>>);
+
+#[derive(Bundle)]
+struct NodeBundle {
+    index: Index,
+    #[bundle]
+    transform: TransformBundle,
+    prev_pos: PreviousPosition,
+    mass: Mass,
+    force: Force,
+    uv: Uv,
+}
+
+impl NodeBundle {
+    /// Node at its rest position in the grid
+    fn new(params: &Params, index: Index) -> Self {
+        let pos = Vec3::new(
+            index.x as f32 * params.r[0],
+            -(index.y as f32 * params.r[0]),
+            0.0,
+        );
+
+        let uv = Vec2::new(
+            index.x as f32 / params.num_nodes_x.saturating_sub(1).max(1) as f32,
+            index.y as f32 / params.num_nodes_y.saturating_sub(1).max(1) as f32,
+        );
+
+        Self::at(params, index, pos, uv)
+    }
+
+    fn at(params: &Params, index: Index, pos: Vec3, uv: Vec2) -> Self {
+        NodeBundle {
+            index,
+            transform: TransformBundle::from(Transform::from_translation(pos)),
+            prev_pos: PreviousPosition(pos),
+            mass: Mass(params.m),
+            force: Force(Vec3::default()),
+            uv: Uv(uv),
+        }
+    }
+}
 
 pub struct Simulation {
     pub params: Params,
+    pub mask: ClothMask,
+    /// imported mesh used instead of the grid
+    pub mesh: Option<ObjMesh>,
 }
 
 #[derive(Default, Clone, Copy)]
@@ -34,15 +126,52 @@ pub struct Params {
     pub m: f32,
     /// gravity constant
     pub g: f32,
+    /// direction in which gravity pulls, straight down if zero
+    pub gravity_direction: Vec3,
+    /// floor and walls the cloth collides with
+    pub bounds: WorldBounds,
+    /// handling of cloth pieces cut off from the rest
+    pub fragments: FragmentOptions,
+    pub storage: StorageBackend,
+    /// how the entity storage solves the springs
+    pub spring_solver: SpringSolver,
+    /// detection of and response to exploding simulation
+    pub stability: StabilityOptions,
+    /// format and interval of the cloth surface export
+    pub export: ExportOptions,
+    /// PNG frame sequence of every physics update
+    pub record: RecordOptions,
+    /// bounds for adding and removing substeps
+    pub adaptive_steps: AdaptiveSteps,
+    pub paused: bool,
     /// mouse click will cause so much force (increase +x)
     pub mouse_force: Vec3,
     /// rest lengths: structural, shear (unused), flexion (unused)
     pub r: Vec3,
     /// spring coefficients: structural, shear (unused), flexion(unused)
     pub k: Vec3,
-    /// velocity dampen factor between constraint solving
+    /// limit the distance of every node to its nearest pinned node to the rest path length
+    pub enable_tethers: bool,
+    /// resistance of adjacent faces to folding, from 0 (silk) to 1 (cardboard)
+    pub bending_stiffness: f32,
+    /// velocity dampen factor between constraint solving, used by the multiplicative model
     pub dampen_factor: f32,
+    /// how the velocity of the cloth is damped
+    pub damping: DampingOptions,
     pub enable_wind: bool,
+    pub wind_model: WindModel,
+    /// density of the air, used by the aerodynamic wind model
+    pub air_density: f32,
+    /// aerodynamic drag coefficient of a cloth face
+    pub drag_coefficient: f32,
+    /// aerodynamic lift coefficient of a cloth face
+    pub lift_coefficient: f32,
+    /// speed of the air blowing through the cloth plane along z. In-plane wind only meets
+    /// the flat grid edge-on, this gives its faces something to catch.
+    pub through_wind: f32,
+    /// world space region in which wind waves travel and wrap around, derived from the
+    /// cloth rest layout if not set
+    pub wind_domain: Option<Rect>,
 
     // UI related params
     pub side_panel_width: f32,
@@ -55,18 +184,111 @@ impl Params {
         self.r[1] = self.r[0] * (2.0 as f32).sqrt(); // diagonal shear spring
         self.r[2] = self.r[0] * 2.0; // flexion spring, double the rest length
     }
+
+    /// Gravity acceleration, pulling down when no direction is set
+    pub fn gravity(&self) -> Vec3 {
+        let direction = self
+            .gravity_direction
+            .try_normalize()
+            .unwrap_or(Vec3::NEG_Y);
+        direction * self.g
+    }
+
+    /// Region in which wind waves travel. Without an explicit domain this is the bounding
+    /// box of the cloth at rest, padded by half of its larger side.
+    pub fn wind_domain(&self) -> Rect {
+        if let Some(domain) = self.wind_domain {
+            return domain;
+        }
+
+        let size = Vec2::new(
+            self.num_nodes_x.saturating_sub(1) as f32 * self.r[0],
+            self.num_nodes_y.saturating_sub(1) as f32 * self.r[0],
+        );
+        let padding = Vec2::splat(size.max_element() / 2.0);
+
+        Rect {
+            min: Vec2::new(0.0, -size.y) - padding,
+            max: Vec2::new(size.x, 0.0) + padding,
+        }
+    }
 }
 
 impl Simulation {
     pub fn new(mut params: Params) -> Self {
         params.calc_rest_lengths(params.r[0]);
-        Simulation { params }
+        let mask = ClothMask::rectangle(params.num_nodes_x, params.num_nodes_y);
+        Simulation {
+            params,
+            mask,
+            mesh: None,
+        }
     }
-}
 
-impl Plugin for Simulation {
-    fn build(&self, app: &mut App) {
-        let mut grid: Vec<Vec<Entity>> = Vec::new();
+    /// Uses the shape of the mask instead of a full rectangle, the mask decides the number
+    /// of nodes
+    pub fn with_mask(mut self, mask: ClothMask) -> Self {
+        self.params.num_nodes_x = mask.width;
+        self.params.num_nodes_y = mask.height;
+        self.mask = mask;
+        self
+    }
+
+    /// Uses an imported mesh instead of the grid, every vertex becomes a node
+    pub fn with_mesh(mut self, mesh: ObjMesh) -> Self {
+        self.params.num_nodes_x = mesh.positions.len();
+        self.params.num_nodes_y = 1;
+
+        // The grid based wind domain does not fit the mesh
+        if self.params.wind_domain.is_none() {
+            let layout = mesh.layout(self.params.r[0]);
+            let min = layout.iter().fold(Vec3::splat(f32::MAX), |a, b| a.min(*b));
+            let max = layout.iter().fold(Vec3::splat(f32::MIN), |a, b| a.max(*b));
+            let padding = Vec2::splat((max - min).truncate().max_element() / 2.0);
+
+            self.params.wind_domain = Some(Rect {
+                min: min.truncate() - padding,
+                max: max.truncate() + padding,
+            });
+        }
+
+        self.mesh = Some(mesh);
+        self
+    }
+
+    /// Spawns the cloth and inserts the physics resources, without any UI or rendering.
+    /// Benchmarks and tests use it to run the physics headless.
+    pub fn setup_world(&self, world: &mut World) {
+        ComputeTaskPool::init(TaskPool::default);
+
+        world.insert_resource(self.params);
+        world.insert_resource(self.mask.clone());
+        world.init_resource::<EdgeColoring>();
+        world.init_resource::<ClothStorage>();
+        world.init_resource::<Snapshot>();
+        world.init_resource::<StabilityWarning>();
+        world.init_resource::<Substeps>();
+        world.init_resource::<Topology>();
+        world.init_resource::<Tethers>();
+        world.init_resource::<Bending>();
+        world.init_resource::<Exporter>();
+        world.init_resource::<Recorder>();
+        world.init_resource::<Fragments>();
+
+        if let Some(mesh) = &self.mesh {
+            world.insert_resource(mesh.clone());
+            SystemStage::single(setup_mesh_system).run(world);
+        } else {
+            self.spawn_grid(world);
+            SystemStage::single(setup_edges_system).run(world);
+        }
+
+        SystemStage::single(setup_wind).run(world);
+    }
+
+    /// Spawns a node for every cell of the mask and inserts the grid and its faces
+    fn spawn_grid(&self, world: &mut World) {
+        let mut grid: Vec<Vec<Option<Entity>>> = Vec::new();
 
         let shape = shapes::Circle {
             radius: self.params.node_size,
@@ -75,140 +297,254 @@ impl Plugin for Simulation {
 
         // Create nodes
         for k in 0..self.params.num_nodes_y {
-            let mut vec: Vec<Entity> = Vec::new();
+            let mut vec: Vec<Option<Entity>> = Vec::new();
 
             for i in 0..self.params.num_nodes_x {
+                let cell = self.mask.cell(i, k);
+                if cell == Cell::Empty {
+                    vec.push(None);
+                    continue;
+                }
+
                 let index = Index {
                     x: i as usize,
                     y: k as usize,
                 };
-                let pos = Transform::from_xyz(
-                    i as f32 * self.params.r[0],
-                    -(k as f32 * self.params.r[0]),
-                    0.0,
-                );
+                let node = NodeBundle::new(&self.params, index);
 
-                let prev_pos = PreviousPosition(Vec3::new(
-                    i as f32 * self.params.r[0],
-                    -(k as f32 * self.params.r[0]),
-                    0.0,
-                ));
                 let _shape_bundle = GeometryBuilder::build_as(
                     &shape,
                     DrawMode::Outlined {
                         fill_mode: FillMode::color(Color::WHITE),
                         outline_mode: StrokeMode::new(Color::BLACK, 1.0),
                     },
-                    pos,
+                    node.transform.local,
                 );
-                let mass = Mass(self.params.m);
-                let force = Force(Vec3::default());
-
-                let id;
-                if k == 0 {
-                    id = app
-                        .world
-                        .spawn()
-                        .insert(index)
-                        .insert_bundle(TransformBundle::from(pos))
-                        .insert(prev_pos)
-                        .insert(mass)
-                        .insert(force)
-                        .insert(Pinned {})
-                        //.insert_bundle(_shape_bundle)
-                        .id();
-                } else {
-                    id = app
-                        .world
-                        .spawn()
-                        .insert(index)
-                        .insert_bundle(TransformBundle::from(pos))
-                        .insert(prev_pos)
-                        .insert(mass)
-                        .insert(force)
-                        //.insert_bundle(_shape_bundle)
-                        .id();
+
+                let mut entity = world.spawn();
+                entity.insert_bundle(node);
+                //entity.insert_bundle(_shape_bundle);
+                if cell == Cell::Pin {
+                    entity.insert(Pinned {});
                 }
 
-                vec.push(id);
+                vec.push(Some(entity.id()));
             }
 
             grid.push(vec);
         }
 
+        let grid = Grid(grid);
+        let faces = Faces::from_grid(&grid);
+
+        world.insert_resource(grid);
+        world.insert_resource(faces);
+    }
+
+    /// Adds the physics stage after the update stage, together with the systems it relies on
+    /// in other stages. The plugin runs it with a fixed timestep, tests on every update.
+    pub fn add_physics_stage(app: &mut App, label: impl StageLabel, stage: SystemStage) {
+        app.add_stage_after(CoreStage::Update, label, stage)
+            .add_system_to_stage(CoreStage::PostUpdate, track_topology);
+    }
+
+    /// Systems of a single physics update, the plugin runs them with a fixed timestep
+    pub fn physics_stage() -> SystemStage {
+        SystemStage::parallel()
+            .with_system_set(
+                SystemSet::new()
+                    .with_run_criteria(run_if_wind_enabled)
+                    .with_system(apply_wind)
+                    .label("apply_wind"),
+            )
+            .with_system(track_topology.label("track_topology"))
+            .with_system(color_edges.label("color_edges"))
+            .with_system(
+                update_tethers
+                    .label("update_tethers")
+                    .after("track_topology"),
+            )
+            .with_system(update_bending.label("update_bending"))
+            .with_system(
+                physics_update
+                    .label("physics_update")
+                    .after("apply_wind")
+                    .after("color_edges")
+                    .after("update_tethers")
+                    .after("update_bending"),
+            )
+            .with_system(
+                physics_update_contiguous
+                    .label("physics_update")
+                    .after("track_topology")
+                    .after("apply_wind")
+                    .after("update_tethers")
+                    .after("update_bending"),
+            )
+            .with_system(
+                check_stability
+                    .label("check_stability")
+                    .after("physics_update"),
+            )
+            .with_system(
+                adapt_substeps
+                    .label("adapt_substeps")
+                    .after("check_stability"),
+            )
+            .with_system(record_frames.after("adapt_substeps"))
+            .with_system(
+                update_fragments
+                    .label("update_fragments")
+                    .after("track_topology"),
+            )
+            .with_system(
+                handle_detached_fragments
+                    .after("update_fragments")
+                    .after("adapt_substeps"),
+            )
+    }
+}
+
+impl Plugin for Simulation {
+    fn build(&self, app: &mut App) {
+        self.setup_world(&mut app.world);
+        Self::add_physics_stage(
+            app,
+            FixedUpdateStage,
+            Self::physics_stage().with_run_criteria(FixedTimestep::step(self.params.dt.into())),
+        );
+
         app.add_plugin(EguiPlugin)
             .add_plugin(DebugLinesPlugin::default())
-            .insert_resource(self.params)
-            .insert_resource(Grid(grid))
-            .add_startup_system(setup_edges_system)
+            .init_resource::<EnergyHistory>()
+            .insert_resource(Bindings::load())
             .add_startup_system(setup_camera)
-            .add_startup_system(setup_wind)
             .add_startup_system(update_canvas_size)
-            .add_system(ui_side_panel)
-            .add_stage_after(
-                CoreStage::Update,
+            .add_system(ui_side_panel.label("ui_side_panel"))
+            .add_system(export_svg.after("ui_side_panel"))
+            .add_system(handle_shortcuts.after("ui_side_panel"))
+            .add_system_to_stage(
                 FixedUpdateStage,
-                SystemStage::parallel()
-                    .with_run_criteria(FixedTimestep::step(self.params.dt.into()))
-                    .with_system(handle_mouse_interaction.label("handle_mouse_interaction"))
-                    .with_system_set(
-                        SystemSet::new()
-                            .with_run_criteria(run_if_wind_enabled)
-                            .with_system(apply_wind)
-                            .label("apply_wind")
-                            .after("handle_mouse_interaction"),
-                    )
-                    .with_system(physics_update.label("physics_update").after("apply_wind")),
+                handle_mouse_interaction
+                    .label("handle_mouse_interaction")
+                    .before("apply_wind")
+                    .before("physics_update"),
             )
-            .add_system(render_edges.after("physics_update"));
+            .add_system_to_stage(FixedUpdateStage, record_energy.after("adapt_substeps"))
+            .add_system_to_stage(FixedUpdateStage, export_frames.after("adapt_substeps"))
+            .add_system(render_edges.after("physics_update"))
+            .add_system(render_wind_sources.with_run_criteria(run_if_wind_enabled))
+            .add_system(render_bounds);
+
+        #[cfg(target_arch = "wasm32")]
+        app.add_startup_system(gestures::web::listen_to_canvas_touches)
+            .add_system_to_stage(
+                FixedUpdateStage,
+                handle_js_commands
+                    .before("apply_wind")
+                    .before("physics_update"),
+            )
+            .add_system_to_stage(
+                FixedUpdateStage,
+                publish_node_positions.after("adapt_substeps"),
+            );
     }
 }
 
-fn setup_edges_system(commands: Commands, params: ResMut<Params>, grid: Res<Grid>) {
-    setup_edges(commands, grid, params.num_nodes_x, params.num_nodes_y);
+fn setup_edges_system(mut commands: Commands, grid: Res<Grid>) {
+    setup_edges(&mut commands, &grid);
 }
 
-/// Creates edges between the nodes in Grid
-fn setup_edges(mut commands: Commands, grid: Res<Grid>, num_nodes_x: usize, num_nodes_y: usize) {
-    for k in 0..num_nodes_y {
-        for i in 0..num_nodes_x {
+/// Creates edges between neighbouring nodes in Grid
+fn setup_edges(commands: &mut Commands, grid: &Grid) {
+    let mut spawn_edge = |a: Option<Entity>, b: Option<Entity>| {
+        if let (Some(a), Some(b)) = (a, b) {
+            let line = shapes::Line(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0));
+
+            commands
+                .spawn()
+                .insert(Edge {
+                    a,
+                    b,
+                    rest_length: None,
+                })
+                .insert_bundle(GeometryBuilder::build_as(
+                    &line,
+                    DrawMode::Stroke(StrokeMode::new(Color::WHITE, 1.0)),
+                    Transform::default(),
+                ));
+        }
+    };
+
+    for (k, row) in grid.0.iter().enumerate() {
+        for i in 0..row.len() {
             // Add top edge
             if k > 0 {
-                let line = shapes::Line(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0));
-
-                commands
-                    .spawn()
-                    .insert(Edge {
-                        a: grid.0[k - 1][i],
-                        b: grid.0[k][i],
-                    })
-                    .insert_bundle(GeometryBuilder::build_as(
-                        &line,
-                        DrawMode::Stroke(StrokeMode::new(Color::WHITE, 1.0)),
-                        Transform::default(),
-                    ));
+                spawn_edge(grid.0[k - 1][i], grid.0[k][i]);
             }
 
             // Add left edge
             if i > 0 {
-                let line = shapes::Line(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0));
-
-                commands
-                    .spawn()
-                    .insert(Edge {
-                        a: grid.0[k][i - 1],
-                        b: grid.0[k][i],
-                    })
-                    .insert_bundle(GeometryBuilder::build_as(
-                        &line,
-                        DrawMode::Stroke(StrokeMode::new(Color::WHITE, 1.0)),
-                        Transform::default(),
-                    ));
+                spawn_edge(grid.0[k][i - 1], grid.0[k][i]);
             }
         }
     }
 }
 
+fn setup_mesh_system(mut commands: Commands, params: Res<Params>, mesh: Res<ObjMesh>) {
+    let (grid, faces) = spawn_mesh(&mut commands, &params, &mesh);
+    commands.insert_resource(grid);
+    commands.insert_resource(faces);
+}
+
+/// Creates a node for every vertex and an edge for every side of the mesh. The vertices at
+/// the top are pinned, like the top row of the grid. The grid holds all nodes in one row.
+fn spawn_mesh(commands: &mut Commands, params: &Params, mesh: &ObjMesh) -> (Grid, Faces) {
+    let layout = mesh.layout(params.r[0]);
+
+    // Texture coordinates are projected onto the bounding box of the mesh
+    let size = layout
+        .iter()
+        .fold(Vec2::ZERO, |size, pos| size.max(Vec2::new(pos.x, -pos.y)))
+        .max(Vec2::splat(f32::EPSILON));
+
+    let nodes: Vec<Entity> = layout
+        .iter()
+        .enumerate()
+        .map(|(i, pos)| {
+            let uv = Vec2::new(pos.x, -pos.y) / size;
+            let mut entity =
+                commands.spawn_bundle(NodeBundle::at(params, Index { x: i, y: 0 }, *pos, uv));
+            if pos.y >= -0.01 * params.r[0] {
+                entity.insert(Pinned {});
+            }
+            entity.id()
+        })
+        .collect();
+
+    for [a, b] in mesh.edges() {
+        let line = shapes::Line(Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0));
+
+        commands
+            .spawn()
+            .insert(Edge {
+                a: nodes[a],
+                b: nodes[b],
+                rest_length: Some(layout[a].distance(layout[b])),
+            })
+            .insert_bundle(GeometryBuilder::build_as(
+                &line,
+                DrawMode::Stroke(StrokeMode::new(Color::WHITE, 1.0)),
+                Transform::default(),
+            ));
+    }
+
+    let faces = Faces::from_triangles(&nodes, &mesh.triangles);
+    let grid = Grid(vec![nodes.into_iter().map(Some).collect()]);
+
+    (grid, faces)
+}
+
 fn setup_camera(mut commands: Commands, windows: Res<Windows>) {
     let window = util::get_primary_window_size(&windows);
     println!("window size: {}", window);
@@ -226,42 +562,158 @@ fn setup_camera(mut commands: Commands, windows: Res<Windows>) {
         )));
 }
 
-fn setup_wind(mut commands: Commands, windows: Res<Windows>) {
-    let window = util::get_primary_window_size(&windows);
-
-    println!("window size: {}", window);
-
-    commands
-        .spawn()
-        .insert(WindWave {
-            rect: Rect {
-                min: Vec2::new(0.0, -1000.0),
-                max: Vec2::new(window.x, 0.0),
-            },
-        })
-        .insert(Force(Vec3::new(1000.0, 300.0, 0.0)));
+fn setup_wind(mut commands: Commands, params: Res<Params>) {
+    commands.spawn().insert(WindSource::Directional {
+        rect: params.wind_domain(),
+        force: Vec3::new(1000.0, 300.0, 0.0),
+    });
 }
 
 fn render_edges(
     mut lines: ResMut<DebugLines>,
+    params: Res<Params>,
+    fragments: Res<Fragments>,
     mut edges: Query<&Edge>,
     mut nodes: Query<(Entity, &Transform), With<Index>>,
 ) {
     for edge in edges.iter_mut() {
         let [(_, a_pos), (_, b_pos)] = nodes.many_mut([edge.a, edge.b]);
-        lines.line(a_pos.translation, b_pos.translation, 0.0);
+
+        match fragments.of_node(edge.a).filter(|_| params.fragments.color) {
+            Some(fragment) => lines.line_colored(
+                a_pos.translation,
+                b_pos.translation,
+                0.0,
+                Fragments::color(fragment),
+            ),
+            None => lines.line(a_pos.translation, b_pos.translation, 0.0),
+        }
+    }
+}
+
+/// Visualizes the region or origin of every wind source
+fn render_wind_sources(mut lines: ResMut<DebugLines>, sources: Query<&WindSource>) {
+    let color = Color::rgb(0.3, 0.7, 1.0);
+
+    for source in sources.iter() {
+        match *source {
+            WindSource::Directional { rect, force } => {
+                let corners = [
+                    Vec3::new(rect.min.x, rect.min.y, 0.0),
+                    Vec3::new(rect.max.x, rect.min.y, 0.0),
+                    Vec3::new(rect.max.x, rect.max.y, 0.0),
+                    Vec3::new(rect.min.x, rect.max.y, 0.0),
+                ];
+                for i in 0..corners.len() {
+                    let next = corners[(i + 1) % corners.len()];
+                    lines.line_colored(corners[i], next, 0.0, color);
+                }
+
+                let center = ((rect.min + rect.max) / 2.0).extend(0.0);
+                lines.line_colored(
+                    center,
+                    center + force.normalize_or_zero() * 50.0,
+                    0.0,
+                    color,
+                );
+            }
+            WindSource::Fan {
+                origin,
+                direction,
+                radius,
+                ..
+            } => {
+                let origin = origin.extend(0.0);
+                let direction = direction.normalize_or_zero().extend(0.0);
+                render_circle(&mut lines, origin, radius, color);
+                lines.line_colored(origin, origin + direction * radius, 0.0, color);
+            }
+            WindSource::Vortex { center, radius, .. } => {
+                let center = center.extend(0.0);
+                render_circle(&mut lines, center, radius, color);
+                render_circle(&mut lines, center, 10.0, color);
+            }
+        }
+    }
+}
+
+fn render_circle(lines: &mut DebugLines, center: Vec3, radius: f32, color: Color) {
+    let segments = 48;
+
+    for i in 0..segments {
+        let angle = |i: usize| i as f32 / segments as f32 * std::f32::consts::TAU;
+        let a = center + Vec3::new(angle(i).cos(), angle(i).sin(), 0.0) * radius;
+        let b = center + Vec3::new(angle(i + 1).cos(), angle(i + 1).sin(), 0.0) * radius;
+        lines.line_colored(a, b, 0.0, color);
+    }
+}
+
+/// Draws the enabled floor and walls
+fn render_bounds(mut lines: ResMut<DebugLines>, params: Res<Params>) {
+    let color = Color::rgb(0.8, 0.6, 0.3);
+    let extent = 100000.0;
+
+    if let Some(floor) = params.bounds.floor {
+        lines.line_colored(
+            Vec3::new(-extent, floor, 0.0),
+            Vec3::new(extent, floor, 0.0),
+            0.0,
+            color,
+        );
+    }
+
+    for wall in [params.bounds.left_wall, params.bounds.right_wall]
+        .into_iter()
+        .flatten()
+    {
+        lines.line_colored(
+            Vec3::new(wall, -extent, 0.0),
+            Vec3::new(wall, extent, 0.0),
+            0.0,
+            color,
+        );
     }
 }
 
+/// Nodes with everything a reset moves back
+pub type ResetNodes<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Index,
+        &'static mut Transform,
+        &'static mut PreviousPosition,
+        &'static mut Force,
+    ),
+>;
+
 /// Resets nodes to initial position
+#[allow(clippy::too_many_arguments)]
 pub fn reset_nodes_position(
-    mut commands: Commands,
+    commands: &mut Commands,
     params: &ResMut<Params>,
-    grid: Res<Grid>,
+    mask: &ClothMask,
+    mesh: Option<&ObjMesh>,
+    grid: &mut Grid,
+    faces: &mut Faces,
     mut edges: Query<Entity, With<Edge>>,
-    mut nodes: Query<(&Index, &mut Transform, &mut PreviousPosition)>,
+    mut nodes: ResetNodes,
 ) {
-    for (index, mut pos, mut prev_pos) in nodes.iter_mut() {
+    // Imported meshes are simply spawned again
+    if let Some(mesh) = mesh {
+        for entity in nodes
+            .iter()
+            .map(|(entity, _, _, _, _)| entity)
+            .chain(edges.iter())
+        {
+            commands.entity(entity).despawn();
+        }
+        (*grid, *faces) = spawn_mesh(commands, params, mesh);
+        return;
+    }
+
+    for (entity, index, mut pos, mut prev_pos, mut force) in nodes.iter_mut() {
         pos.translation = Vec3::new(
             index.x as f32 * params.r[0],
             -(index.y as f32 * params.r[0]),
@@ -269,12 +721,38 @@ pub fn reset_nodes_position(
         );
 
         prev_pos.0 = pos.translation.clone();
+        force.0 = Vec3::ZERO;
+
+        commands.entity(entity).remove::<Asleep>();
+    }
+
+    // Respawn nodes of fragments which were despawned
+    let mut respawned = false;
+    for (k, row) in grid.0.iter_mut().enumerate() {
+        for (i, node) in row.iter_mut().enumerate() {
+            let cell = mask.cell(i, k);
+            if cell == Cell::Empty || node.is_some_and(|node| nodes.get(node).is_ok()) {
+                continue;
+            }
+
+            let mut entity = commands.spawn_bundle(NodeBundle::new(params, Index { x: i, y: k }));
+            if cell == Cell::Pin {
+                entity.insert(Pinned {});
+            }
+
+            *node = Some(entity.id());
+            respawned = true;
+        }
+    }
+
+    if respawned {
+        *faces = Faces::from_grid(grid);
     }
 
     for entity in edges.iter_mut() {
         commands.entity(entity).despawn();
     }
-    setup_edges(commands, grid, params.num_nodes_x, params.num_nodes_y);
+    setup_edges(commands, grid);
 }
 
 /// Make sure the canvas is full screen on web
@@ -0,0 +1,65 @@
+use super::mesh::{Faces, Links};
+use super::wind::WindNodes;
+use super::Params;
+use bevy::prelude::*;
+
+/// Applies drag and lift to every intact face, based on the velocity of the face relative to
+/// the surrounding air. A face facing the wind catches the most, a face edge-on nothing, so
+/// the flat grid only feels the in-plane wind together with air blowing through its plane.
+pub fn apply_aerodynamic_forces(
+    dt: f32,
+    params: &Params,
+    faces: &Faces,
+    links: &Links,
+    air_velocity: impl Fn(Vec3) -> Vec3,
+    nodes: &mut WindNodes,
+) {
+    for face in faces.0.iter().filter(|face| face.is_intact(links)) {
+        // Faces touching sleeping or removed nodes are skipped
+        let states = match nodes.get_many(face.nodes) {
+            Ok(states) => states,
+            Err(_) => continue,
+        };
+
+        let positions = states.map(|(pos, _, _, _)| pos.translation);
+        let velocity = states
+            .iter()
+            .map(|(pos, prev_pos, _, _)| (pos.translation - prev_pos.0) / dt)
+            .fold(Vec3::ZERO, |a, b| a + b)
+            / 3.0;
+
+        let centroid = (positions[0] + positions[1] + positions[2]) / 3.0;
+        let relative_velocity = velocity - air_velocity(centroid);
+        let speed = relative_velocity.length();
+
+        let cross = (positions[1] - positions[0]).cross(positions[2] - positions[0]);
+        let area = 0.5 * cross.length();
+
+        if speed <= f32::EPSILON || area <= f32::EPSILON {
+            continue;
+        }
+
+        let direction = relative_velocity / speed;
+        let mut normal = cross.normalize();
+
+        // Orient the normal towards the oncoming air, the winding of the face is arbitrary
+        if normal.dot(direction) < 0.0 {
+            normal = -normal;
+        }
+        let cos_theta = normal.dot(direction);
+
+        let dynamic_pressure = 0.5 * params.air_density * speed * speed * area;
+        let drag = -params.drag_coefficient * dynamic_pressure * cos_theta * direction;
+        let lift = params.lift_coefficient
+            * dynamic_pressure
+            * cos_theta
+            * normal.cross(direction).cross(direction);
+
+        let node_force = (drag + lift) / 3.0;
+        for node in face.nodes {
+            if let Ok((_, _, mut force, None)) = nodes.get_mut(node) {
+                force.0 += node_force;
+            }
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use super::mesh::{Face, Faces, Links};
+use super::physics::{Index, SolverNodes};
+use super::Params;
+use bevy::prelude::*;
+
+/// Two faces sharing a side, bending keeps the angle between them at its rest value
+pub struct BendingConstraint {
+    /// the shared side first, then the opposite node of each face
+    pub nodes: [Entity; 4],
+    pub faces: [Face; 2],
+    /// angle between the face normals at rest
+    pub rest_angle: f32,
+}
+
+#[derive(Default)]
+pub struct Bending(pub Vec<BendingConstraint>);
+
+/// Pairs up adjacent faces whenever the faces change, the current positions are the rest
+/// state. Faces only change when the cloth is created or reset.
+pub fn update_bending(
+    faces: Res<Faces>,
+    mut bending: ResMut<Bending>,
+    nodes: Query<&Transform, With<Index>>,
+) {
+    if !faces.is_changed() {
+        return;
+    }
+
+    let mut by_side: HashMap<(Entity, Entity), Vec<Face>> = HashMap::new();
+    for face in faces.0.iter() {
+        let [a, b, c] = face.nodes;
+        for (x, y) in [(a, b), (b, c), (c, a)] {
+            let key = if x < y { (x, y) } else { (y, x) };
+            by_side.entry(key).or_default().push(*face);
+        }
+    }
+
+    let mut constraints = Vec::new();
+    for ((a, b), adjacent) in by_side {
+        if let [first, second] = adjacent[..] {
+            let opposite = |face: Face| {
+                *face
+                    .nodes
+                    .iter()
+                    .find(|node| **node != a && **node != b)
+                    .unwrap()
+            };
+            let corners = [a, b, opposite(first), opposite(second)];
+
+            if let Ok(positions) = nodes.get_many(corners) {
+                let positions = positions.map(|pos| pos.translation);
+                constraints.push(BendingConstraint {
+                    nodes: corners,
+                    faces: [first, second],
+                    rest_angle: dihedral_angle(positions),
+                });
+            }
+        }
+    }
+
+    // Keep the solving order stable between runs
+    constraints.sort_by_key(|constraint| constraint.nodes);
+    bending.0 = constraints;
+}
+
+/// Solves the bending constraints of intact face pairs once
+pub fn apply_bending(
+    params: &Res<Params>,
+    bending: &Bending,
+    links: &Links,
+    nodes: &mut SolverNodes,
+) {
+    for constraint in bending.0.iter() {
+        if !constraint.faces.iter().all(|face| face.is_intact(links)) {
+            continue;
+        }
+
+        let mut states = match nodes.get_many_mut(constraint.nodes) {
+            Ok(states) => states,
+            Err(_) => continue,
+        };
+
+        let positions = [0, 1, 2, 3].map(|i| states[i].0.translation);
+        let inverse_masses = [0, 1, 2, 3].map(|i| match &states[i] {
+            (_, _, _, mass, None) => 1.0 / mass.0,
+            _ => 0.0,
+        });
+
+        let corrections = bending_corrections(
+            positions,
+            inverse_masses,
+            constraint.rest_angle,
+            params.bending_stiffness,
+        );
+
+        for ((pos, _, _, _, _), correction) in states.iter_mut().zip(corrections) {
+            pos.translation += correction;
+        }
+    }
+}
+
+/// Signed angle between the normals of the two faces, zero for a flat pair. The sign tells
+/// which way the pair is folded.
+pub fn dihedral_angle(positions: [Vec3; 4]) -> f32 {
+    let [a, b, c, d] = positions;
+    let n1 = (c - a).cross(c - b).normalize_or_zero();
+    let n2 = (d - b).cross(d - a).normalize_or_zero();
+    let edge = (b - a).normalize_or_zero();
+
+    n1.cross(n2).dot(edge).atan2(n1.dot(n2))
+}
+
+/// Position corrections which move the dihedral angle towards the rest angle, following
+/// the position based dynamics bending constraint. The gradient of the signed angle does
+/// not vanish on a flat pair, so a flat cloth resists folding too. Stiffness goes from 0
+/// to 1.
+pub fn bending_corrections(
+    positions: [Vec3; 4],
+    inverse_masses: [f32; 4],
+    rest_angle: f32,
+    stiffness: f32,
+) -> [Vec3; 4] {
+    let no_correction = [Vec3::ZERO; 4];
+
+    let [a, b, c, d] = positions;
+    let edge = b - a;
+    let length = edge.length();
+    let n1 = (c - a).cross(c - b);
+    let n2 = (d - b).cross(d - a);
+    let (area_1, area_2) = (n1.length_squared(), n2.length_squared());
+    if length <= f32::EPSILON || area_1 <= f32::EPSILON || area_2 <= f32::EPSILON {
+        return no_correction;
+    }
+
+    // Gradients of the angle for the shared side and the opposite nodes, after Bridson et
+    // al., "Simulation of clothing with folds and wrinkles"
+    let (m1, m2) = (n1 / area_1, n2 / area_2);
+    let gradients = [
+        -((c - b).dot(edge) * m1 + (d - b).dot(edge) * m2) / length,
+        ((c - a).dot(edge) * m1 + (d - a).dot(edge) * m2) / length,
+        -length * m1,
+        -length * m2,
+    ];
+
+    let denominator: f32 = gradients
+        .iter()
+        .zip(inverse_masses)
+        .map(|(q, w)| w * q.length_squared())
+        .sum();
+    if denominator <= f32::EPSILON {
+        return no_correction;
+    }
+
+    // Take the short way round between the two angles
+    let mut difference = dihedral_angle(positions) - rest_angle;
+    if difference > PI {
+        difference -= TAU;
+    } else if difference < -PI {
+        difference += TAU;
+    }
+
+    let scale = stiffness * difference / denominator;
+    [0, 1, 2, 3].map(|i| -inverse_masses[i] * scale * gradients[i])
+}
@@ -0,0 +1,351 @@
+use bevy::prelude::*;
+
+/// Where the bindings are kept between runs, a file in the working directory on desktop and
+/// the local storage of the browser on web
+const BINDINGS_STORE: &str = "bindings.cfg";
+
+/// Something the user can do with a key or a mouse button
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Force,
+    Cut,
+    Pan,
+    ZoomIn,
+    ZoomOut,
+    Reset,
+    Pause,
+    ToggleWind,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::Force,
+        Action::Cut,
+        Action::Pan,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::Reset,
+        Action::Pause,
+        Action::ToggleWind,
+    ];
+
+    /// Key of the action in the stored bindings
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Force => "force",
+            Action::Cut => "cut",
+            Action::Pan => "pan",
+            Action::ZoomIn => "zoom_in",
+            Action::ZoomOut => "zoom_out",
+            Action::Reset => "reset",
+            Action::Pause => "pause",
+            Action::ToggleWind => "toggle_wind",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Force => "Force",
+            Action::Cut => "Cut",
+            Action::Pan => "Pan",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::Reset => "Reset",
+            Action::Pause => "Pause",
+            Action::ToggleWind => "Toggle wind",
+        }
+    }
+}
+
+/// Key or mouse button triggering an action
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl Binding {
+    /// Every binding which can be chosen in the side panel
+    pub fn choices() -> impl Iterator<Item = Binding> {
+        [MouseButton::Left, MouseButton::Right, MouseButton::Middle]
+            .into_iter()
+            .map(Binding::Mouse)
+            .chain(KEYS.into_iter().map(Binding::Key))
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?} key", key),
+            Binding::Mouse(button) => format!("{:?} mouse button", button).to_lowercase(),
+        }
+    }
+
+    /// Written as `key:Space` or `mouse:Left`
+    fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("key:{:?}", key),
+            Binding::Mouse(button) => format!("mouse:{:?}", button),
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::choices().find(|binding| binding.name() == name)
+    }
+}
+
+/// Keys which can be bound, modifiers included for trackpads without a middle button
+const KEYS: [KeyCode; 61] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Space,
+    KeyCode::Tab,
+    KeyCode::Return,
+    KeyCode::Back,
+    KeyCode::Escape,
+    KeyCode::Left,
+    KeyCode::Up,
+    KeyCode::Right,
+    KeyCode::Down,
+    KeyCode::LShift,
+    KeyCode::LControl,
+    KeyCode::LAlt,
+    KeyCode::RShift,
+    KeyCode::RControl,
+    KeyCode::RAlt,
+    KeyCode::Minus,
+    KeyCode::Equals,
+    KeyCode::Comma,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Semicolon,
+    KeyCode::Apostrophe,
+    KeyCode::LBracket,
+    KeyCode::RBracket,
+    KeyCode::Backslash,
+];
+
+/// Keys and mouse buttons of a frame, with whether the side panel leaves them to the
+/// simulation
+pub struct BindingInput<'a> {
+    pub keys: &'a Input<KeyCode>,
+    pub buttons: &'a Input<MouseButton>,
+    /// the side panel has no text field focused
+    pub keyboard_free: bool,
+    /// the pointer is not over the side panel
+    pub pointer_free: bool,
+}
+
+/// Binding of every action, an action without one can only be done from the side panel
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bindings(pub Vec<(Action, Option<Binding>)>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings(vec![
+            (Action::Force, Some(Binding::Mouse(MouseButton::Left))),
+            (Action::Cut, Some(Binding::Mouse(MouseButton::Right))),
+            (Action::Pan, Some(Binding::Mouse(MouseButton::Middle))),
+            (Action::ZoomIn, Some(Binding::Key(KeyCode::Equals))),
+            (Action::ZoomOut, Some(Binding::Key(KeyCode::Minus))),
+            (Action::Reset, Some(Binding::Key(KeyCode::R))),
+            (Action::Pause, Some(Binding::Key(KeyCode::Space))),
+            (Action::ToggleWind, Some(Binding::Key(KeyCode::W))),
+        ])
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, action: Action) -> Option<Binding> {
+        self.0
+            .iter()
+            .find(|(other, _)| *other == action)
+            .and_then(|(_, binding)| *binding)
+    }
+
+    pub fn set(&mut self, action: Action, binding: Option<Binding>) {
+        match self.0.iter_mut().find(|(other, _)| *other == action) {
+            Some((_, current)) => *current = binding,
+            None => self.0.push((action, binding)),
+        }
+    }
+
+    /// Held down, ignoring the keyboard or the pointer while the side panel uses them
+    pub fn pressed(&self, action: Action, input: &BindingInput) -> bool {
+        match self.get(action) {
+            Some(Binding::Key(key)) => input.keyboard_free && input.keys.pressed(key),
+            Some(Binding::Mouse(button)) => input.pointer_free && input.buttons.pressed(button),
+            None => false,
+        }
+    }
+
+    /// Pressed on this frame, ignoring the keyboard or the pointer while the side panel uses
+    /// them
+    pub fn just_pressed(&self, action: Action, input: &BindingInput) -> bool {
+        match self.get(action) {
+            Some(Binding::Key(key)) => input.keyboard_free && input.keys.just_pressed(key),
+            Some(Binding::Mouse(button)) => {
+                input.pointer_free && input.buttons.just_pressed(button)
+            }
+            None => false,
+        }
+    }
+
+    /// Other actions sharing the binding of the action
+    pub fn conflicts(&self, action: Action) -> Vec<Action> {
+        let binding = match self.get(action) {
+            Some(binding) => binding,
+            None => return Vec::new(),
+        };
+
+        self.0
+            .iter()
+            .filter(|(other, other_binding)| *other != action && *other_binding == Some(binding))
+            .map(|(other, _)| *other)
+            .collect()
+    }
+
+    /// One `action = binding` per line, `none` for unbound actions
+    pub fn to_config(&self) -> String {
+        self.0
+            .iter()
+            .map(|(action, binding)| {
+                let binding = binding.map_or("none".to_string(), |binding| binding.name());
+                format!("{} = {}\n", action.name(), binding)
+            })
+            .collect()
+    }
+
+    /// Reads what `to_config` wrote, actions missing from it keep their default
+    pub fn from_config(source: &str) -> Result<Self, String> {
+        let mut bindings = Bindings::default();
+
+        for (number, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected action = binding"))?;
+            let action = Action::ALL
+                .into_iter()
+                .find(|action| action.name() == name.trim())
+                .ok_or_else(|| error("unknown action"))?;
+            let binding = match value.trim() {
+                "none" => None,
+                value => Some(Binding::parse(value).ok_or_else(|| error("unknown binding"))?),
+            };
+            bindings.set(action, binding);
+        }
+
+        Ok(bindings)
+    }
+
+    /// Stored bindings, the defaults if there are none or they can't be read
+    pub fn load() -> Self {
+        let source = match load_store() {
+            Some(source) => source,
+            None => return Bindings::default(),
+        };
+
+        Self::from_config(&source).unwrap_or_else(|err| {
+            eprintln!("ignoring stored bindings: {}", err);
+            Bindings::default()
+        })
+    }
+
+    pub fn save(&self) {
+        if let Err(err) = save_store(&self.to_config()) {
+            eprintln!("could not store bindings: {}", err);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_store() -> Option<String> {
+    std::fs::read_to_string(BINDINGS_STORE).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_store(contents: &str) -> Result<(), String> {
+    std::fs::write(BINDINGS_STORE, contents).map_err(|err| err.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_store() -> Option<String> {
+    web_sys::window()?
+        .local_storage()
+        .ok()??
+        .get_item(BINDINGS_STORE)
+        .ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_store(contents: &str) -> Result<(), String> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or("local storage is not available")?
+        .set_item(BINDINGS_STORE, contents)
+        .map_err(|_| "local storage is full or disabled".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bindings_round_trip() {
+        let mut bindings = Bindings::default();
+        bindings.set(Action::Pan, Some(Binding::Key(KeyCode::LShift)));
+        bindings.set(Action::ToggleWind, None);
+
+        assert_eq!(Bindings::from_config(&bindings.to_config()), Ok(bindings));
+        assert!(Bindings::from_config("fly = key:F").is_err());
+    }
+
+    #[test]
+    fn bindings_report_shared_keys() {
+        let mut bindings = Bindings::default();
+        assert!(bindings.conflicts(Action::Reset).is_empty());
+
+        bindings.set(Action::Pause, Some(Binding::Key(KeyCode::R)));
+        assert_eq!(bindings.conflicts(Action::Reset), vec![Action::Pause]);
+        assert_eq!(bindings.conflicts(Action::Pause), vec![Action::Reset]);
+    }
+}
@@ -0,0 +1,73 @@
+use super::physics::SolverNodes;
+use bevy::prelude::*;
+
+/// Optional floor and walls which keep the cloth inside the world
+#[derive(Default, Clone, Copy)]
+pub struct WorldBounds {
+    /// y coordinate of the floor
+    pub floor: Option<f32>,
+    /// x coordinate of the left wall
+    pub left_wall: Option<f32>,
+    /// x coordinate of the right wall
+    pub right_wall: Option<f32>,
+    /// fraction of the normal velocity kept after bouncing off a bound
+    pub restitution: f32,
+    /// fraction of the tangential velocity lost when touching a bound
+    pub friction: f32,
+}
+
+impl WorldBounds {
+    /// Planes of the enabled bounds, given by their inward normal and a point on the plane
+    pub fn planes(&self) -> Vec<(Vec3, Vec3)> {
+        let mut planes = Vec::new();
+
+        if let Some(floor) = self.floor {
+            planes.push((Vec3::Y, Vec3::new(0.0, floor, 0.0)));
+        }
+        if let Some(left_wall) = self.left_wall {
+            planes.push((Vec3::X, Vec3::new(left_wall, 0.0, 0.0)));
+        }
+        if let Some(right_wall) = self.right_wall {
+            planes.push((-Vec3::X, Vec3::new(right_wall, 0.0, 0.0)));
+        }
+
+        planes
+    }
+
+    /// Pushes a node which went through any of the planes back and reflects its velocity
+    pub fn collide(&self, planes: &[(Vec3, Vec3)], pos: &mut Vec3, prev_pos: &mut Vec3) {
+        for (normal, point) in planes.iter() {
+            let depth = (*pos - *point).dot(*normal);
+            if depth >= 0.0 {
+                continue;
+            }
+
+            // Verlet velocity, split into the part along the normal and along the bound
+            let velocity = *pos - *prev_pos;
+            let normal_velocity = velocity.dot(*normal).min(0.0) * *normal;
+            let tangent_velocity = velocity - normal_velocity;
+
+            let velocity =
+                -self.restitution * normal_velocity + (1.0 - self.friction) * tangent_velocity;
+
+            *pos -= depth * *normal;
+            *prev_pos = *pos - velocity;
+        }
+    }
+}
+
+/// Pushes nodes which went through a bound back and reflects their velocity
+pub fn apply_bounds(bounds: &WorldBounds, nodes: &mut SolverNodes) {
+    let planes = bounds.planes();
+    if planes.is_empty() {
+        return;
+    }
+
+    for (mut pos, mut prev_pos, _, _, pinned) in nodes.iter_mut() {
+        if pinned.is_some() {
+            continue;
+        }
+
+        bounds.collide(&planes, &mut pos.translation, &mut prev_pos.0);
+    }
+}
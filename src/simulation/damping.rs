@@ -0,0 +1,84 @@
+use bevy::prelude::*;
+
+/// How the velocity of the cloth is damped
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum DampingModel {
+    /// Multiplies the velocity by `dampen_factor` every substep, depends on the substep count
+    #[default]
+    Multiplicative,
+    /// Slows down every node proportionally to its velocity
+    AirDrag,
+    /// Damps only the relative velocity of the two nodes along every edge
+    SpringAxis,
+    /// Air drag proportional to the mass plus spring-axis damping proportional to the stiffness
+    Rayleigh,
+}
+
+/// Damping coefficients are rates per second, so they do not depend on the timestep
+#[derive(Default, Clone, Copy)]
+pub struct DampingOptions {
+    pub model: DampingModel,
+    /// rate of the air drag
+    pub air_drag: f32,
+    /// rate of the spring-axis damping
+    pub spring_damping: f32,
+    /// mass proportional Rayleigh coefficient
+    pub rayleigh_alpha: f32,
+    /// stiffness proportional Rayleigh coefficient
+    pub rayleigh_beta: f32,
+}
+
+impl DampingOptions {
+    /// Factor the velocity of every node is multiplied with in a substep
+    pub fn velocity_factor(&self, dampen_factor: f32, dt: f32) -> f32 {
+        match self.model {
+            DampingModel::Multiplicative => dampen_factor,
+            DampingModel::AirDrag => (-self.air_drag * dt).exp(),
+            DampingModel::SpringAxis => 1.0,
+            DampingModel::Rayleigh => (-self.rayleigh_alpha * dt).exp(),
+        }
+    }
+
+    /// Whether the model damps the stretch velocity of the edges
+    pub fn damps_edges(&self) -> bool {
+        matches!(
+            self.model,
+            DampingModel::SpringAxis | DampingModel::Rayleigh
+        )
+    }
+
+    /// Rate at which the stretch velocity of an edge decays, zero if edges are not damped
+    pub fn edge_rate(&self, k: f32, a_inverse_mass: f32, b_inverse_mass: f32) -> f32 {
+        match self.model {
+            DampingModel::SpringAxis => self.spring_damping,
+            DampingModel::Rayleigh => self.rayleigh_beta * k * (a_inverse_mass + b_inverse_mass),
+            _ => 0.0,
+        }
+    }
+}
+
+/// Removes part of the relative velocity of two nodes along their edge, by moving their
+/// previous positions. Momentum is kept, the correction is split by the inverse masses.
+#[allow(clippy::too_many_arguments)]
+pub fn damp_edge(
+    rate: f32,
+    dt: f32,
+    a_pos: Vec3,
+    a_prev_pos: &mut Vec3,
+    a_inverse_mass: f32,
+    b_pos: Vec3,
+    b_prev_pos: &mut Vec3,
+    b_inverse_mass: f32,
+) {
+    let total_inverse_mass = a_inverse_mass + b_inverse_mass;
+    if total_inverse_mass <= 0.0 {
+        return;
+    }
+
+    let axis = (a_pos - b_pos).normalize_or_zero();
+    let stretch_velocity = ((a_pos - *a_prev_pos) - (b_pos - *b_prev_pos)).dot(axis);
+    let correction = stretch_velocity * (1.0 - (-rate * dt).exp()) * axis;
+
+    *a_prev_pos += correction * a_inverse_mass / total_inverse_mass;
+    *b_prev_pos -= correction * b_inverse_mass / total_inverse_mass;
+}
@@ -0,0 +1,214 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use super::fragments::Asleep;
+use super::physics::{Awake, Edge, Mass, PreviousPosition};
+use super::substeps::Substeps;
+use super::Params;
+use bevy::prelude::*;
+
+/// Number of physics updates kept in the history
+const HISTORY_LENGTH: usize = 600;
+
+/// Energy and momentum of the cloth after a single physics update
+#[derive(Clone, Copy)]
+pub struct EnergySample {
+    /// simulated time in seconds
+    pub time: f32,
+    pub kinetic: f32,
+    pub gravitational: f32,
+    pub spring: f32,
+    pub momentum: Vec3,
+}
+
+impl EnergySample {
+    pub fn total(&self) -> f32 {
+        self.kinetic + self.gravitational + self.spring
+    }
+}
+
+/// Recent energy samples, oldest first
+#[derive(Default)]
+pub struct EnergyHistory {
+    pub samples: VecDeque<EnergySample>,
+    /// simulated time of the next sample, accumulated since the timestep may change
+    time: f32,
+}
+
+impl EnergyHistory {
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "time,kinetic,gravitational,spring,total,momentum_x,momentum_y,momentum_z\n",
+        );
+
+        for sample in self.samples.iter() {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{},{},{}",
+                sample.time,
+                sample.kinetic,
+                sample.gravitational,
+                sample.spring,
+                sample.total(),
+                sample.momentum.x,
+                sample.momentum.y,
+                sample.momentum.z
+            )
+            .unwrap();
+        }
+
+        csv
+    }
+}
+
+/// Computes the energy of the cloth after every physics update. Velocities are estimated
+/// from the last substep, gravitational energy is relative to the origin. Nothing is
+/// recorded while paused.
+pub fn record_energy(
+    params: Res<Params>,
+    substeps: Res<Substeps>,
+    mut history: ResMut<EnergyHistory>,
+    edges: Query<&Edge, Without<Asleep>>,
+    nodes: Query<(&Transform, &PreviousPosition, &Mass), Awake>,
+) {
+    if params.paused {
+        return;
+    }
+
+    let step_dt = substeps.dt(params.dt);
+    let gravity = params.gravity();
+
+    let mut sample = EnergySample {
+        time: history.time,
+        kinetic: 0.0,
+        gravitational: 0.0,
+        spring: 0.0,
+        momentum: Vec3::ZERO,
+    };
+
+    for (pos, prev_pos, mass) in nodes.iter() {
+        let velocity = (pos.translation - prev_pos.0) / step_dt;
+
+        sample.kinetic += 0.5 * mass.0 * velocity.length_squared();
+        sample.gravitational -= mass.0 * gravity.dot(pos.translation);
+        sample.momentum += mass.0 * velocity;
+    }
+
+    for edge in edges.iter() {
+        if let Ok([(a_pos, _, _), (b_pos, _, _)]) = nodes.get_many([edge.a, edge.b]) {
+            let stretch =
+                a_pos.translation.distance(b_pos.translation) - edge.rest_length(params.r[0]);
+            sample.spring += 0.5 * params.k[0] * stretch * stretch;
+        }
+    }
+
+    history.time += params.dt;
+    history.samples.push_back(sample);
+    while history.samples.len() > HISTORY_LENGTH {
+        history.samples.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::physics::Index;
+
+    /// World with one node moving at `velocity` and a second one at rest, joined by an edge
+    /// stretched to twice its rest length
+    fn world(params: Params, velocity: Vec3) -> World {
+        let mut world = World::new();
+        world.insert_resource(params);
+        world.init_resource::<Substeps>();
+        world.init_resource::<EnergyHistory>();
+
+        let step_dt = Substeps::default().dt(params.dt);
+        let mut spawn_node = |x: usize, pos: Vec3, velocity: Vec3| {
+            world
+                .spawn()
+                .insert_bundle((
+                    Index { x, y: 0 },
+                    Transform::from_translation(pos),
+                    PreviousPosition(pos - velocity * step_dt),
+                    Mass(2.0),
+                ))
+                .id()
+        };
+        let a = spawn_node(0, Vec3::new(0.0, 10.0, 0.0), velocity);
+        let b = spawn_node(1, Vec3::new(40.0, 10.0, 0.0), Vec3::ZERO);
+        world.spawn().insert(Edge {
+            a,
+            b,
+            rest_length: Some(20.0),
+        });
+
+        world
+    }
+
+    fn params() -> Params {
+        Params {
+            dt: 0.02,
+            g: 10.0,
+            k: Vec3::new(3.0, 0.0, 0.0),
+            ..Default::default()
+        }
+    }
+
+    fn record(world: &mut World, updates: usize) {
+        let mut stage = SystemStage::single(record_energy);
+        for _ in 0..updates {
+            stage.run(world);
+        }
+    }
+
+    #[test]
+    fn sample_sums_the_energy_of_nodes_and_edges() {
+        let mut world = world(params(), Vec3::new(3.0, 4.0, 0.0));
+        record(&mut world, 1);
+
+        let history = world.resource::<EnergyHistory>();
+        let sample = history.samples.back().unwrap();
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-2;
+
+        // 0.5 * 2 * 5², both nodes at a height of 10 and a stretch of 20
+        assert!(close(sample.kinetic, 25.0), "kinetic {}", sample.kinetic);
+        assert!(close(sample.gravitational, 400.0));
+        assert!(close(sample.spring, 600.0));
+        assert!(close(sample.total(), 1025.0));
+        assert!(sample.momentum.distance(Vec3::new(6.0, 8.0, 0.0)) < 1e-2);
+    }
+
+    #[test]
+    fn history_keeps_the_simulated_time_of_recent_updates() {
+        let mut world = world(params(), Vec3::ZERO);
+        record(&mut world, HISTORY_LENGTH + 10);
+
+        let history = world.resource::<EnergyHistory>();
+        assert_eq!(history.samples.len(), HISTORY_LENGTH);
+        let first = history.samples.front().unwrap().time;
+        assert!((first - 10.0 * params().dt).abs() < 1e-4);
+
+        // Nothing is recorded while paused
+        world.resource_mut::<Params>().paused = true;
+        record(&mut world, 5);
+        let history = world.resource::<EnergyHistory>();
+        assert_eq!(history.samples.len(), HISTORY_LENGTH);
+        assert!((history.time - (HISTORY_LENGTH + 10) as f32 * params().dt).abs() < 1e-3);
+    }
+
+    #[test]
+    fn csv_has_a_row_per_sample() {
+        let mut world = world(params(), Vec3::new(3.0, 4.0, 0.0));
+        record(&mut world, 3);
+
+        let csv = world.resource::<EnergyHistory>().to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[0],
+            "time,kinetic,gravitational,spring,total,momentum_x,momentum_y,momentum_z"
+        );
+        assert!(lines[1].starts_with("0,"));
+        assert!(lines.iter().all(|line| line.split(',').count() == 8));
+    }
+}
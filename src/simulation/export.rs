@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+
+use super::mesh::{Faces, Links};
+use super::physics::{Edge, Index};
+use super::Params;
+use bevy::prelude::*;
+
+/// Directory the exported frames are written to, relative to the working directory
+const EXPORT_DIRECTORY: &str = "export";
+
+/// File format of the exported cloth surface
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    #[default]
+    Obj,
+    Gltf,
+}
+
+impl ExportFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Obj => "obj",
+            ExportFormat::Gltf => "gltf",
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// export every n-th physics update, zero to only export on request
+    pub interval: u32,
+    /// color SVG edges by how much they are stretched or compressed
+    pub svg_strain_colors: bool,
+    /// draw SVG node circles of `node_size`
+    pub svg_nodes: bool,
+}
+
+/// Texture coordinate of a node with the origin at the top left, fixed at spawn
+#[derive(Component, Clone, Copy)]
+pub struct Uv(pub Vec2);
+
+/// Nodes with everything written to the exported files
+type ExportNodes<'w, 's> = Query<'w, 's, (&'static Transform, &'static Uv), With<Index>>;
+
+/// State of the frame export
+#[derive(Default)]
+pub struct Exporter {
+    /// export the current frame on the next physics update
+    pub requested: bool,
+    /// number of frames written so far
+    pub frames: u64,
+    ticks: u64,
+    /// write an SVG snapshot on the next update
+    pub svg_requested: bool,
+    /// number of SVG snapshots written so far
+    pub snapshots: u64,
+}
+
+/// Triangles of the cloth surface which are not torn
+pub struct SurfaceMesh {
+    pub positions: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl SurfaceMesh {
+    /// Collects the faces without a cut side, nodes not used by any of them are left out
+    pub fn from_faces(faces: &Faces, links: &Links, nodes: &ExportNodes) -> Self {
+        let mut mesh = SurfaceMesh {
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            triangles: Vec::new(),
+        };
+        let mut vertices: HashMap<Entity, u32> = HashMap::new();
+
+        for face in faces.0.iter().filter(|face| face.is_complete(links)) {
+            let states = match nodes.get_many(face.nodes) {
+                Ok(states) => states,
+                Err(_) => continue,
+            };
+
+            let mut triangle = [0; 3];
+            for (i, (node, (pos, uv))) in face.nodes.iter().zip(states).enumerate() {
+                triangle[i] = *vertices.entry(*node).or_insert_with(|| {
+                    mesh.positions.push(pos.translation);
+                    mesh.uvs.push(uv.0);
+                    mesh.positions.len() as u32 - 1
+                });
+            }
+            mesh.triangles.push(triangle);
+        }
+
+        mesh
+    }
+
+    pub fn to_obj(&self) -> String {
+        let mut obj = String::from("# cloth simulation frame\n");
+
+        for pos in self.positions.iter() {
+            writeln!(obj, "v {} {} {}", pos.x, pos.y, pos.z).unwrap();
+        }
+        // OBJ texture coordinates start at the bottom left
+        for uv in self.uvs.iter() {
+            writeln!(obj, "vt {} {}", uv.x, 1.0 - uv.y).unwrap();
+        }
+        // Indices start at one and are shared by positions and texture coordinates
+        for [a, b, c] in self
+            .triangles
+            .iter()
+            .map(|triangle| triangle.map(|i| i + 1))
+        {
+            writeln!(obj, "f {}/{} {}/{} {}/{}", a, a, b, b, c, c).unwrap();
+        }
+
+        obj
+    }
+
+    /// glTF 2.0 with the buffer embedded as a data URI
+    pub fn to_gltf(&self) -> String {
+        let mut buffer: Vec<u8> = Vec::new();
+        for pos in self.positions.iter() {
+            for value in pos.to_array() {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let uvs_offset = buffer.len();
+        for uv in self.uvs.iter() {
+            for value in uv.to_array() {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let indices_offset = buffer.len();
+        for index in self.triangles.iter().flatten() {
+            buffer.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let min = self
+            .positions
+            .iter()
+            .fold(Vec3::splat(f32::MAX), |a, b| a.min(*b));
+        let max = self
+            .positions
+            .iter()
+            .fold(Vec3::splat(f32::MIN), |a, b| a.max(*b));
+        let vertex_count = self.positions.len();
+
+        format!(
+            r#"{{
+  "asset": {{ "version": "2.0", "generator": "cloth-simulation-bevy-rust" }},
+  "scene": 0,
+  "scenes": [{{ "nodes": [0] }}],
+  "nodes": [{{ "mesh": 0, "name": "cloth" }}],
+  "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }}, "indices": 2 }}] }}],
+  "buffers": [{{ "byteLength": {}, "uri": "data:application/octet-stream;base64,{}" }}],
+  "bufferViews": [
+    {{ "buffer": 0, "byteOffset": 0, "byteLength": {}, "target": 34962 }},
+    {{ "buffer": 0, "byteOffset": {}, "byteLength": {}, "target": 34962 }},
+    {{ "buffer": 0, "byteOffset": {}, "byteLength": {}, "target": 34963 }}
+  ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": {}, "type": "VEC3", "min": [{}, {}, {}], "max": [{}, {}, {}] }},
+    {{ "bufferView": 1, "componentType": 5126, "count": {}, "type": "VEC2" }},
+    {{ "bufferView": 2, "componentType": 5125, "count": {}, "type": "SCALAR" }}
+  ]
+}}
+"#,
+            buffer.len(),
+            base64(&buffer),
+            uvs_offset,
+            uvs_offset,
+            indices_offset - uvs_offset,
+            indices_offset,
+            buffer.len() - indices_offset,
+            vertex_count,
+            min.x,
+            min.y,
+            min.z,
+            max.x,
+            max.y,
+            max.z,
+            vertex_count,
+            self.triangles.len() * 3,
+        )
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(value >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Writes the cloth surface when requested from the UI or every `interval` physics updates
+pub fn export_frames(
+    params: Res<Params>,
+    mut exporter: ResMut<Exporter>,
+    faces: Res<Faces>,
+    edges: Query<&Edge>,
+    nodes: ExportNodes,
+) {
+    let options = params.export;
+    if !params.paused {
+        exporter.ticks += 1;
+    }
+
+    let scheduled = options.interval > 0
+        && !params.paused
+        && exporter.ticks.is_multiple_of(options.interval as u64);
+    if !exporter.requested && !scheduled {
+        return;
+    }
+    exporter.requested = false;
+
+    let links = Links::from_edges(edges.iter());
+    let mesh = SurfaceMesh::from_faces(&faces, &links, &nodes);
+    // An empty glTF accessor has no valid bounds, and an empty OBJ is of no use either
+    if mesh.triangles.is_empty() {
+        println!("nothing to export, every face of the cloth is torn");
+        return;
+    }
+    let contents = match options.format {
+        ExportFormat::Obj => mesh.to_obj(),
+        ExportFormat::Gltf => mesh.to_gltf(),
+    };
+
+    let path = format!(
+        "{}/frame_{:05}.{}",
+        EXPORT_DIRECTORY,
+        exporter.frames,
+        options.format.extension()
+    );
+    match fs::create_dir_all(EXPORT_DIRECTORY).and_then(|_| fs::write(&path, contents)) {
+        Ok(_) => {
+            println!("frame written to {}", path);
+            exporter.frames += 1;
+        }
+        Err(err) => eprintln!("could not write {}: {}", path, err),
+    }
+}
+
+/// Color of an edge, blue when compressed, white at rest and red when stretched by a fifth
+fn strain_color(strain: f32) -> [u8; 3] {
+    let t = (strain / 0.2).clamp(-1.0, 1.0);
+    let fade = (255.0 * (1.0 - t.abs())) as u8;
+
+    if t >= 0.0 {
+        [255, fade, fade]
+    } else {
+        [fade, fade, 255]
+    }
+}
+
+/// Vector snapshot of the current edges, y points up like in the simulation
+pub fn cloth_svg(params: &Params, edges: &Query<&Edge>, nodes: &ExportNodes) -> String {
+    let options = params.export;
+    let radius = if options.svg_nodes {
+        params.node_size
+    } else {
+        0.0
+    };
+
+    let (min, max) = nodes.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), (pos, _)| {
+            let point = Vec2::new(pos.translation.x, -pos.translation.y);
+            (min.min(point), max.max(point))
+        },
+    );
+    let padding = Vec2::splat(radius + 1.0);
+    let (min, size) = (min - padding, max - min + 2.0 * padding);
+
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        min.x, min.y, size.x, size.y, size.x, size.y
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="rgb(102,102,102)"/>"#,
+        min.x, min.y, size.x, size.y
+    )
+    .unwrap();
+
+    for edge in edges.iter() {
+        if let Ok([(a_pos, _), (b_pos, _)]) = nodes.get_many([edge.a, edge.b]) {
+            let (a, b) = (a_pos.translation, b_pos.translation);
+            let [red, green, blue] = if options.svg_strain_colors {
+                let rest_length = edge.rest_length(params.r[0]);
+                strain_color((a.distance(b) - rest_length) / rest_length)
+            } else {
+                [255, 255, 255]
+            };
+
+            writeln!(
+                svg,
+                r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="rgb({},{},{})" stroke-width="1"/>"#,
+                a.x, -a.y, b.x, -b.y, red, green, blue
+            )
+            .unwrap();
+        }
+    }
+
+    if radius > 0.0 {
+        for (pos, _) in nodes.iter() {
+            writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="white" stroke="black"/>"#,
+                pos.translation.x, -pos.translation.y, radius
+            )
+            .unwrap();
+        }
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Writes an SVG snapshot when requested from the UI
+pub fn export_svg(
+    params: Res<Params>,
+    mut exporter: ResMut<Exporter>,
+    edges: Query<&Edge>,
+    nodes: ExportNodes,
+) {
+    if !exporter.svg_requested {
+        return;
+    }
+    exporter.svg_requested = false;
+
+    let path = format!("{}/cloth_{:05}.svg", EXPORT_DIRECTORY, exporter.snapshots);
+    let contents = cloth_svg(&params, &edges, &nodes);
+    match fs::create_dir_all(EXPORT_DIRECTORY).and_then(|_| fs::write(&path, contents)) {
+        Ok(_) => {
+            println!("snapshot written to {}", path);
+            exporter.snapshots += 1;
+        }
+        Err(err) => eprintln!("could not write {}: {}", path, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::SystemState;
+
+    #[test]
+    fn base64_matches_the_rfc_vectors() {
+        for (bytes, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(base64(bytes.as_bytes()), encoded);
+        }
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+        assert_eq!(base64(&[0x00, 0x10, 0x83]), "ABCD");
+    }
+
+    #[test]
+    fn gltf_layout_follows_the_mesh() {
+        let mesh = SurfaceMesh {
+            positions: vec![
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(1.0, -1.0, 2.0),
+                Vec3::new(0.0, -1.0, 0.0),
+            ],
+            uvs: vec![Vec2::ZERO, Vec2::X, Vec2::ONE, Vec2::Y],
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+        };
+        let gltf = mesh.to_gltf();
+
+        // 4 positions of 12 bytes, 4 texture coordinates of 8 and 6 indices of 4
+        for expected in [
+            r#""byteLength": 104, "uri": "data:application/octet-stream;base64,"#,
+            r#""byteOffset": 0, "byteLength": 48, "target": 34962"#,
+            r#""byteOffset": 48, "byteLength": 32, "target": 34962"#,
+            r#""byteOffset": 80, "byteLength": 24, "target": 34963"#,
+            r#""count": 4, "type": "VEC3", "min": [0, -1, 0], "max": [1, 0, 2]"#,
+            r#""count": 4, "type": "VEC2""#,
+            r#""count": 6, "type": "SCALAR""#,
+        ] {
+            assert!(gltf.contains(expected), "{} not in {}", expected, gltf);
+        }
+
+        let uri = gltf.split("base64,").nth(1).unwrap();
+        let data = &uri[..uri.find('"').unwrap()];
+        assert_eq!(data.len(), 104_usize.div_ceil(3) * 4);
+    }
+
+    #[test]
+    fn obj_indices_start_at_one() {
+        let mesh = SurfaceMesh {
+            positions: vec![Vec3::ZERO, Vec3::X, Vec3::NEG_Y],
+            uvs: vec![Vec2::ZERO, Vec2::X, Vec2::Y],
+            triangles: vec![[0, 1, 2]],
+        };
+        let obj = mesh.to_obj();
+        assert!(obj.contains("v 1 0 0\n"));
+        assert!(obj.contains("vt 0 1\nvt 1 1\nvt 0 0\n"));
+        assert!(obj.ends_with("f 1/1 2/2 3/3\n"));
+    }
+
+    #[test]
+    fn strain_colors_fade_from_white() {
+        assert_eq!(strain_color(0.0), [255, 255, 255]);
+        assert_eq!(strain_color(0.1), [255, 127, 127]);
+        assert_eq!(strain_color(0.5), [255, 0, 0]);
+        assert_eq!(strain_color(-0.1), [127, 127, 255]);
+        assert_eq!(strain_color(-1.0), [0, 0, 255]);
+    }
+
+    #[test]
+    fn svg_draws_the_edges_upside_down() {
+        let mut world = World::new();
+        let mut spawn_node = |x: f32, y: f32| {
+            world
+                .spawn()
+                .insert_bundle((
+                    Index { x: 0, y: 0 },
+                    Transform::from_xyz(x, y, 0.0),
+                    Uv(Vec2::ZERO),
+                ))
+                .id()
+        };
+        let (a, b) = (spawn_node(0.0, 10.0), spawn_node(30.0, 10.0));
+        world.spawn().insert(Edge {
+            a,
+            b,
+            rest_length: Some(20.0),
+        });
+
+        let mut params = Params {
+            node_size: 4.0,
+            ..Default::default()
+        };
+        params.export.svg_strain_colors = true;
+        params.export.svg_nodes = true;
+
+        let mut state: SystemState<(Query<&Edge>, ExportNodes)> = SystemState::new(&mut world);
+        let (edges, nodes) = state.get(&world);
+        let svg = cloth_svg(&params, &edges, &nodes);
+
+        // Padded by the node radius and a pixel
+        assert!(svg.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-5 -15 40 10" width="40" height="10">"#
+        ));
+        assert!(svg.contains(
+            r#"<line x1="0" y1="-10" x2="30" y2="-10" stroke="rgb(255,0,0)" stroke-width="1"/>"#
+        ));
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert!(svg.ends_with("</svg>\n"));
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+use super::physics::{Edge, Index, Pinned, PreviousPosition};
+use super::topology::Topology;
+use super::ui::MainCamera;
+use super::Params;
+use bevy::prelude::*;
+use bevy::sprite::Rect;
+
+/// Nodes moving less than this per substep are considered at rest
+const REST_DISTANCE: f32 = 0.01;
+
+/// Number of consecutive physics updates a fragment has to rest before it is put to sleep,
+/// so a piece cut from a resting cloth gets to fall first
+const REST_TICKS: u32 = 30;
+
+/// What happens to fragments which are not connected to any pinned node
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum DetachedAction {
+    /// Keep simulating them
+    #[default]
+    Simulate,
+    /// Stop simulating them once they leave the view or come to rest
+    Sleep,
+    /// Remove them once they leave the view
+    Despawn,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct FragmentOptions {
+    /// draw every fragment in its own color
+    pub color: bool,
+    pub detached_action: DetachedAction,
+}
+
+/// Marks nodes and edges of a sleeping fragment, these are skipped by the physics
+#[derive(Component)]
+pub struct Asleep;
+
+/// Nodes and edges connected to each other
+pub struct Fragment {
+    pub nodes: Vec<Entity>,
+    pub edges: Vec<Entity>,
+    /// if any of the nodes is pinned
+    pub pinned: bool,
+    /// consecutive physics updates all nodes were at rest
+    resting_ticks: u32,
+}
+
+/// Connected components of the edge graph
+#[derive(Default)]
+pub struct Fragments {
+    pub fragments: Vec<Fragment>,
+    of_node: HashMap<Entity, usize>,
+    /// topology generation the fragments were calculated for
+    generation: u64,
+}
+
+impl Fragments {
+    /// Index of the fragment the node belongs to
+    pub fn of_node(&self, node: Entity) -> Option<usize> {
+        self.of_node.get(&node).copied()
+    }
+
+    /// Distinct color for every fragment
+    pub fn color(index: usize) -> Color {
+        let hue = (index as f32 * 137.5) % 360.0;
+        Color::hsl(hue, 0.8, 0.6)
+    }
+}
+
+/// Recalculates fragments whenever the topology changes. Runs in every physics update, so
+/// each one sees the cuts of the previous one.
+pub fn update_fragments(
+    topology: Res<Topology>,
+    mut fragments: ResMut<Fragments>,
+    edges: Query<(Entity, &Edge)>,
+    nodes: Query<(Entity, Option<&Pinned>), With<Index>>,
+) {
+    if fragments.generation == topology.generation {
+        return;
+    }
+
+    let index: HashMap<Entity, usize> = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (entity, _))| (entity, i))
+        .collect();
+
+    // Union-find over the nodes, joined by edges
+    let mut parent: Vec<usize> = (0..index.len()).collect();
+    for (_, edge) in edges.iter() {
+        if let (Some(&a), Some(&b)) = (index.get(&edge.a), index.get(&edge.b)) {
+            let a = find(&mut parent, a);
+            let b = find(&mut parent, b);
+            parent[a] = b;
+        }
+    }
+
+    let mut of_root: HashMap<usize, usize> = HashMap::new();
+    let mut result: Vec<Fragment> = Vec::new();
+    let mut of_node = HashMap::new();
+
+    for (i, (entity, pinned)) in nodes.iter().enumerate() {
+        let root = find(&mut parent, i);
+        let fragment = *of_root.entry(root).or_insert_with(|| {
+            result.push(Fragment {
+                nodes: Vec::new(),
+                edges: Vec::new(),
+                pinned: false,
+                resting_ticks: 0,
+            });
+            result.len() - 1
+        });
+
+        result[fragment].nodes.push(entity);
+        result[fragment].pinned |= pinned.is_some();
+        of_node.insert(entity, fragment);
+    }
+
+    for (entity, edge) in edges.iter() {
+        if let Some(&fragment) = of_node.get(&edge.a) {
+            result[fragment].edges.push(entity);
+        }
+    }
+
+    *fragments = Fragments {
+        fragments: result,
+        of_node,
+        generation: topology.generation,
+    };
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Puts detached fragments to sleep or despawns them, depending on the options. Without a
+/// window, like in headless runs, every fragment counts as visible.
+pub fn handle_detached_fragments(
+    mut commands: Commands,
+    params: Res<Params>,
+    mut fragments: ResMut<Fragments>,
+    windows: Option<Res<Windows>>,
+    camera: Query<&Transform, With<MainCamera>>,
+    nodes: Query<(&Transform, &PreviousPosition, Option<&Asleep>), With<Index>>,
+) {
+    let action = params.fragments.detached_action;
+    if action == DetachedAction::Simulate || params.paused {
+        return;
+    }
+
+    let view = windows.and_then(|windows| visible_rect(&windows, &camera));
+    let is_visible = |pos: Vec3| {
+        view.is_none_or(|view| {
+            pos.x >= view.min.x && pos.x <= view.max.x && pos.y >= view.min.y && pos.y <= view.max.y
+        })
+    };
+
+    for fragment in fragments
+        .fragments
+        .iter_mut()
+        .filter(|fragment| !fragment.pinned)
+    {
+        let states: Vec<_> = fragment
+            .nodes
+            .iter()
+            .filter_map(|node| nodes.get(*node).ok())
+            .collect();
+
+        if states.is_empty() || states.iter().any(|(_, _, asleep)| asleep.is_some()) {
+            continue;
+        }
+
+        let out_of_view = states
+            .iter()
+            .all(|(pos, _, _)| !is_visible(pos.translation));
+        let resting = states
+            .iter()
+            .all(|(pos, prev_pos, _)| pos.translation.distance(prev_pos.0) < REST_DISTANCE);
+        fragment.resting_ticks = if resting {
+            fragment.resting_ticks + 1
+        } else {
+            0
+        };
+
+        match action {
+            // Edges cut in the same physics update are despawned before these commands run,
+            // so entities which are gone by then are skipped
+            DetachedAction::Sleep if out_of_view || fragment.resting_ticks >= REST_TICKS => {
+                for &entity in fragment.nodes.iter().chain(fragment.edges.iter()) {
+                    commands.add(move |world: &mut World| {
+                        if let Some(mut entity) = world.get_entity_mut(entity) {
+                            entity.insert(Asleep);
+                        }
+                    });
+                }
+            }
+            DetachedAction::Despawn if out_of_view => {
+                for &entity in fragment.nodes.iter().chain(fragment.edges.iter()) {
+                    commands.add(move |world: &mut World| {
+                        world.despawn(entity);
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// World space rectangle seen by the main camera
+fn visible_rect(windows: &Windows, camera: &Query<&Transform, With<MainCamera>>) -> Option<Rect> {
+    let window = windows.get_primary()?;
+    let camera = camera.get_single().ok()?;
+
+    let half_size = Vec2::new(window.width(), window.height()) / 2.0 * camera.scale.truncate();
+    let center = camera.translation.truncate();
+
+    Some(Rect {
+        min: center - half_size,
+        max: center + half_size,
+    })
+}
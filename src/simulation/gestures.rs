@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+/// Seconds a finger has to rest to start cutting
+const LONG_PRESS_SECONDS: f64 = 0.5;
+/// Pixels a finger may move and still count as resting
+const LONG_PRESS_DISTANCE: f32 = 10.0;
+
+/// A finger on the screen, in the coordinates of the cursor with y up
+#[derive(Clone, Copy)]
+pub struct Finger {
+    pub id: u64,
+    pub position: Vec2,
+}
+
+/// What the fingers on the screen ask for in an update
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Gesture {
+    Force(Vec2),
+    Cut(Vec2),
+    /// motion of the center of two fingers in screen space with y down, like the mouse
+    /// motion, and the factor the camera scale changes by as they move apart or together
+    PanZoom {
+        pan: Vec2,
+        zoom: Option<f32>,
+    },
+}
+
+/// What a finger on the screen does
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TouchMode {
+    /// not decided yet between force and cut
+    Pending,
+    Force,
+    Cut,
+    /// part of a two finger pan and zoom
+    Gesture,
+}
+
+/// A finger on the screen, followed from where it landed
+#[derive(Clone, Copy)]
+struct TrackedTouch {
+    start_time: f64,
+    start_position: Vec2,
+    last_position: Vec2,
+    mode: TouchMode,
+}
+
+/// Turns the fingers on the screen into gestures. A single finger exerts force once it
+/// moves and cuts once it rests for a while, two fingers pan with their center and zoom
+/// with their distance. A finger which was part of a two finger gesture stays out of force
+/// and cut until it is lifted.
+#[derive(Default)]
+pub struct TouchGestures {
+    tracked: HashMap<u64, TrackedTouch>,
+}
+
+impl TouchGestures {
+    /// Follows the fingers currently on the screen. The fingers are tracked by hand, the
+    /// physics update may not run on the frame a finger lands.
+    pub fn update(&mut self, now: f64, fingers: &[Finger]) -> Option<Gesture> {
+        self.tracked
+            .retain(|id, _| fingers.iter().any(|finger| finger.id == *id));
+
+        let mut fingers = fingers.to_vec();
+        fingers.sort_by_key(|finger| finger.id);
+        for finger in fingers.iter() {
+            self.tracked.entry(finger.id).or_insert(TrackedTouch {
+                start_time: now,
+                start_position: finger.position,
+                last_position: finger.position,
+                mode: TouchMode::Pending,
+            });
+        }
+
+        let gesture = match fingers.as_slice() {
+            [finger] => {
+                let tracked = self.tracked.get_mut(&finger.id).unwrap();
+                if tracked.mode == TouchMode::Pending {
+                    if finger.position.distance(tracked.start_position) > LONG_PRESS_DISTANCE {
+                        tracked.mode = TouchMode::Force;
+                    } else if now - tracked.start_time > LONG_PRESS_SECONDS {
+                        tracked.mode = TouchMode::Cut;
+                    }
+                }
+
+                match tracked.mode {
+                    TouchMode::Force => Some(Gesture::Force(finger.position)),
+                    TouchMode::Cut => Some(Gesture::Cut(finger.position)),
+                    TouchMode::Pending | TouchMode::Gesture => None,
+                }
+            }
+            [a, b] => {
+                let last_a = self.tracked[&a.id].last_position;
+                let last_b = self.tracked[&b.id].last_position;
+
+                let pan = (a.position + b.position - last_a - last_b) / 2.0;
+                let (distance, last_distance) =
+                    (a.position.distance(b.position), last_a.distance(last_b));
+                let zoom = (distance > f32::EPSILON && last_distance > f32::EPSILON)
+                    .then(|| last_distance / distance);
+
+                for finger in [a, b] {
+                    self.tracked.get_mut(&finger.id).unwrap().mode = TouchMode::Gesture;
+                }
+                Some(Gesture::PanZoom {
+                    pan: Vec2::new(pan.x, -pan.y),
+                    zoom,
+                })
+            }
+            _ => None,
+        };
+
+        for finger in fingers {
+            self.tracked.get_mut(&finger.id).unwrap().last_position = finger.position;
+        }
+
+        gesture
+    }
+}
+
+/// winit does not report touches on web, so they are taken from the canvas itself
+#[cfg(target_arch = "wasm32")]
+pub mod web {
+    use std::cell::RefCell;
+
+    use super::Finger;
+    use bevy::prelude::*;
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::JsCast;
+
+    // The listeners and the app share the single thread of the browser
+    thread_local! {
+        /// fingers on the canvas with y down, as of the last touch event
+        static FINGERS: RefCell<Vec<Finger>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Follows the fingers on the canvas. The default actions are not prevented, the side
+    /// panel only sees the mouse events the browser emulates from taps.
+    pub fn listen_to_canvas_touches() {
+        let canvas = match web_sys::window()
+            .and_then(|window| window.document())
+            .and_then(|document| document.query_selector("canvas").ok().flatten())
+        {
+            Some(canvas) => canvas,
+            None => return,
+        };
+
+        let target = canvas.clone();
+        let listener = Closure::wrap(Box::new(move |event: web_sys::TouchEvent| {
+            let rect = target.get_bounding_client_rect();
+            let touches = event.touches();
+            let fingers = (0..touches.length())
+                .filter_map(|i| touches.get(i))
+                .map(|touch| Finger {
+                    id: touch.identifier() as u64,
+                    position: Vec2::new(
+                        touch.client_x() as f32 - rect.left() as f32,
+                        touch.client_y() as f32 - rect.top() as f32,
+                    ),
+                })
+                .collect();
+            FINGERS.with(|current| *current.borrow_mut() = fingers);
+        }) as Box<dyn FnMut(web_sys::TouchEvent)>);
+
+        for event in ["touchstart", "touchmove", "touchend", "touchcancel"] {
+            if canvas
+                .add_event_listener_with_callback(event, listener.as_ref().unchecked_ref())
+                .is_err()
+            {
+                warn!("could not listen to {} on the canvas", event);
+            }
+        }
+        // The listener lives as long as the page
+        listener.forget();
+    }
+
+    /// Fingers on the canvas, the events count from its top, the cursor from its bottom
+    pub fn fingers(wnd: &Window) -> Vec<Finger> {
+        FINGERS.with(|fingers| {
+            fingers
+                .borrow()
+                .iter()
+                .map(|finger| Finger {
+                    id: finger.id,
+                    position: Vec2::new(finger.position.x, wnd.height() - finger.position.y),
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finger(id: u64, x: f32, y: f32) -> Finger {
+        Finger {
+            id,
+            position: Vec2::new(x, y),
+        }
+    }
+
+    #[test]
+    fn moving_finger_exerts_force() {
+        let mut gestures = TouchGestures::default();
+        assert_eq!(gestures.update(0.0, &[finger(1, 100.0, 100.0)]), None);
+        assert_eq!(gestures.update(0.1, &[finger(1, 105.0, 100.0)]), None);
+        assert_eq!(
+            gestures.update(0.2, &[finger(1, 120.0, 100.0)]),
+            Some(Gesture::Force(Vec2::new(120.0, 100.0)))
+        );
+
+        // Resting afterwards does not turn the force into a cut
+        assert_eq!(
+            gestures.update(2.0, &[finger(1, 120.0, 100.0)]),
+            Some(Gesture::Force(Vec2::new(120.0, 100.0)))
+        );
+    }
+
+    #[test]
+    fn resting_finger_cuts() {
+        let mut gestures = TouchGestures::default();
+        assert_eq!(gestures.update(1.0, &[finger(1, 100.0, 100.0)]), None);
+        assert_eq!(gestures.update(1.4, &[finger(1, 104.0, 100.0)]), None);
+        assert_eq!(
+            gestures.update(1.6, &[finger(1, 104.0, 100.0)]),
+            Some(Gesture::Cut(Vec2::new(104.0, 100.0)))
+        );
+
+        // Moving on keeps cutting along the way
+        assert_eq!(
+            gestures.update(1.7, &[finger(1, 200.0, 100.0)]),
+            Some(Gesture::Cut(Vec2::new(200.0, 100.0)))
+        );
+
+        // A finger landing again starts over
+        assert_eq!(gestures.update(1.8, &[]), None);
+        assert_eq!(gestures.update(1.9, &[finger(1, 200.0, 100.0)]), None);
+    }
+
+    #[test]
+    fn two_fingers_pan_and_zoom() {
+        let mut gestures = TouchGestures::default();
+        assert_eq!(
+            gestures.update(0.0, &[finger(1, 100.0, 100.0), finger(2, 200.0, 100.0)]),
+            Some(Gesture::PanZoom {
+                pan: Vec2::ZERO,
+                zoom: Some(1.0)
+            })
+        );
+
+        // The center moves up by 10 with y up, and the fingers twice as far apart zoom in
+        assert_eq!(
+            gestures.update(0.1, &[finger(2, 260.0, 110.0), finger(1, 60.0, 110.0)]),
+            Some(Gesture::PanZoom {
+                pan: Vec2::new(10.0, -10.0),
+                zoom: Some(0.5)
+            })
+        );
+
+        // Fingers on top of each other do not zoom
+        assert_eq!(
+            gestures.update(0.2, &[finger(1, 160.0, 110.0), finger(2, 160.0, 110.0)]),
+            Some(Gesture::PanZoom {
+                pan: Vec2::ZERO,
+                zoom: None
+            })
+        );
+    }
+
+    #[test]
+    fn finger_left_from_a_gesture_does_nothing() {
+        let mut gestures = TouchGestures::default();
+        gestures.update(0.0, &[finger(1, 100.0, 100.0), finger(2, 200.0, 100.0)]);
+        assert_eq!(gestures.update(0.1, &[finger(1, 300.0, 100.0)]), None);
+        assert_eq!(gestures.update(2.0, &[finger(1, 300.0, 100.0)]), None);
+    }
+}
@@ -0,0 +1,303 @@
+// Commands from the page embedding the web build. The queue and the systems compile on every
+// target so they can be tested natively, only the exported functions are web only.
+#![cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+
+use std::cell::RefCell;
+
+use super::mesh::Faces;
+use super::{
+    reset_nodes_position, ClothMask, Edge, Force, Grid, Index, ObjMesh, Params, Pinned, ResetNodes,
+};
+use crate::config;
+use bevy::prelude::*;
+
+/// Request from the page, applied on the next physics update
+#[derive(Clone, Debug)]
+pub enum JsCommand {
+    SetParam(String, String),
+    Reset,
+    SetPaused(bool),
+    /// force added to every free node within the radius of a world position
+    ApplyForce {
+        position: Vec2,
+        force: Vec2,
+        radius: f32,
+    },
+}
+
+// The page and the app share the single thread of the browser, so the commands and the
+// latest positions are simply kept in thread locals
+thread_local! {
+    static COMMANDS: RefCell<Vec<JsCommand>> = const { RefCell::new(Vec::new()) };
+    static POSITIONS: RefCell<Vec<f32>> = const { RefCell::new(Vec::new()) };
+}
+
+fn push(command: JsCommand) {
+    COMMANDS.with(|commands| commands.borrow_mut().push(command));
+}
+
+/// Checks a parameter change right away, so the page gets the error. Parameters only read
+/// at startup are rejected, they can be given in the query string instead.
+fn check_param(key: &str, value: &str) -> Result<(), String> {
+    if config::STARTUP_PARAMS.contains(&key) {
+        return Err(format!(
+            "{} is only read at startup, set it in the query string",
+            key
+        ));
+    }
+    // Catch unknown keys and malformed values, the whole set of parameters is validated
+    // once applied
+    config::set_param(&mut Params::default(), key, value)
+}
+
+/// Positions of all nodes as `[x0, y0, z0, x1, ...]`, row by row, so node `x` of row `y` starts
+/// at `3 * (y * num_nodes_x + x)` on a grid without holes
+fn node_positions() -> Vec<f32> {
+    POSITIONS.with(|positions| positions.borrow().clone())
+}
+
+/// Functions exported to JavaScript, so a page can drive the simulation from its own
+/// controls
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use super::{check_param, push, JsCommand};
+    use bevy::prelude::*;
+    use wasm_bindgen::prelude::*;
+
+    /// Sets a parameter by the same key as the URL and the command line, e.g.
+    /// `set_param("bounds.floor", "none")`
+    #[wasm_bindgen]
+    pub fn set_param(key: &str, value: &str) -> Result<(), JsValue> {
+        check_param(key, value)?;
+        push(JsCommand::SetParam(key.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    #[wasm_bindgen]
+    pub fn reset() {
+        push(JsCommand::Reset);
+    }
+
+    #[wasm_bindgen]
+    pub fn set_paused(paused: bool) {
+        push(JsCommand::SetPaused(paused));
+    }
+
+    /// Adds a force to every free node within the radius of the world position, for a
+    /// single physics update
+    #[wasm_bindgen]
+    pub fn apply_force(x: f32, y: f32, force_x: f32, force_y: f32, radius: f32) {
+        push(JsCommand::ApplyForce {
+            position: Vec2::new(x, y),
+            force: Vec2::new(force_x, force_y),
+            radius,
+        });
+    }
+
+    /// Positions of all nodes as `[x0, y0, z0, x1, ...]`, row by row, so node `x` of row `y`
+    /// starts at `3 * (y * num_nodes_x + x)` on a grid without holes
+    #[wasm_bindgen]
+    pub fn node_positions() -> Vec<f32> {
+        super::node_positions()
+    }
+}
+
+/// Free nodes the page can push around
+type ForceNodes<'w, 's> =
+    Query<'w, 's, (&'static Transform, &'static mut Force), (With<Index>, Without<Pinned>)>;
+
+/// Applies the commands queued by the page since the last physics update. A reset is done
+/// after the parameter changes, so it already uses them, and before the forces.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_js_commands(
+    mut commands: Commands,
+    mut params: ResMut<Params>,
+    mask: Res<ClothMask>,
+    mesh: Option<Res<ObjMesh>>,
+    mut grid: ResMut<Grid>,
+    mut faces: ResMut<Faces>,
+    edges: Query<Entity, With<Edge>>,
+    mut nodes: ParamSet<(ResetNodes, ForceNodes)>,
+) {
+    let queued = COMMANDS.with(|queued| std::mem::take(&mut *queued.borrow_mut()));
+    if queued.is_empty() {
+        return;
+    }
+
+    let mut reset = false;
+    let mut forces = Vec::new();
+    for command in queued {
+        match command {
+            JsCommand::SetParam(key, value) => {
+                let mut changed = *params;
+                let result = config::set_param(&mut changed, &key, &value).and_then(|_| {
+                    // Like the slider, the other rest lengths follow the structural one
+                    if key == "r" {
+                        changed.calc_rest_lengths(changed.r[0]);
+                    }
+                    config::validate_params(&changed)
+                });
+                match result {
+                    Ok(_) => *params = changed,
+                    Err(err) => warn!("ignoring {} = {}: {}", key, value, err),
+                }
+            }
+            JsCommand::Reset => reset = true,
+            JsCommand::SetPaused(paused) => params.paused = paused,
+            JsCommand::ApplyForce {
+                position,
+                force,
+                radius,
+            } => forces.push((position, force, radius)),
+        }
+    }
+
+    if reset {
+        reset_nodes_position(
+            &mut commands,
+            &params,
+            &mask,
+            mesh.as_deref(),
+            &mut grid,
+            &mut faces,
+            edges,
+            nodes.p0(),
+        );
+    }
+
+    // Forces are only consumed by running physics updates
+    if params.paused {
+        return;
+    }
+
+    for (pos, mut node_force) in nodes.p1().iter_mut() {
+        for (position, force, radius) in forces.iter() {
+            if pos.translation.truncate().distance(*position) < *radius {
+                node_force.0 += force.extend(0.0);
+            }
+        }
+    }
+}
+
+/// Shares the node positions of the last physics update with the page
+pub fn publish_node_positions(nodes: Query<(&Index, &Transform)>) {
+    let mut sorted: Vec<(&Index, &Transform)> = nodes.iter().collect();
+    sorted.sort_by_key(|(index, _)| (index.y, index.x));
+
+    POSITIONS.with(|positions| {
+        let mut positions = positions.borrow_mut();
+        positions.clear();
+        positions.extend(
+            sorted
+                .iter()
+                .flat_map(|(_, pos)| pos.translation.to_array()),
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::Simulation;
+
+    fn world() -> World {
+        let mut world = World::new();
+        Simulation::new(Params {
+            num_nodes_x: 4,
+            num_nodes_y: 3,
+            r: Vec3::new(20.0, 0.0, 0.0),
+            ..config::Config::default().params
+        })
+        .setup_world(&mut world);
+        world
+    }
+
+    fn handle(world: &mut World) {
+        SystemStage::single(handle_js_commands).run(world);
+    }
+
+    fn forces(world: &mut World) -> Vec<(Index, Vec3)> {
+        let mut forces: Vec<(Index, Vec3)> = world
+            .query::<(&Index, &Force)>()
+            .iter(world)
+            .map(|(index, force)| (*index, force.0))
+            .collect();
+        forces.sort_by_key(|(index, _)| *index);
+        forces
+    }
+
+    #[test]
+    fn invalid_parameters_are_ignored_in_order() {
+        let mut world = world();
+        assert!(check_param("g", "500").is_ok());
+        assert!(check_param("g", "heavy").is_err());
+        assert!(check_param("dt", "0.01").is_err());
+
+        push(JsCommand::SetParam("g".to_string(), "500".to_string()));
+        push(JsCommand::SetParam("m".to_string(), "-1".to_string()));
+        push(JsCommand::SetPaused(true));
+        push(JsCommand::SetParam("g".to_string(), "700".to_string()));
+        handle(&mut world);
+
+        let params = *world.resource::<Params>();
+        assert_eq!(params.g, 700.0);
+        assert_eq!(params.m, config::Config::default().params.m);
+        assert!(params.paused);
+
+        // The queue was emptied
+        world.resource_mut::<Params>().g = 0.0;
+        handle(&mut world);
+        assert_eq!(world.resource::<Params>().g, 0.0);
+    }
+
+    #[test]
+    fn forces_act_on_free_nodes_within_the_radius() {
+        let mut world = world();
+        push(JsCommand::ApplyForce {
+            position: Vec2::new(20.0, -20.0),
+            force: Vec2::new(5.0, 0.0),
+            radius: 25.0,
+        });
+        handle(&mut world);
+
+        // Node 1x1 and its direct neighbours, except the pinned one in the top row
+        let pushed: Vec<(usize, usize)> = forces(&mut world)
+            .into_iter()
+            .filter(|(_, force)| *force == Vec3::new(5.0, 0.0, 0.0))
+            .map(|(index, _)| (index.x, index.y))
+            .collect();
+        assert_eq!(pushed, vec![(0, 1), (1, 1), (1, 2), (2, 1)]);
+
+        // Paused updates would keep the force around, so it is dropped
+        let mut world = self::world();
+        push(JsCommand::SetPaused(true));
+        push(JsCommand::ApplyForce {
+            position: Vec2::ZERO,
+            force: Vec2::ONE,
+            radius: 1000.0,
+        });
+        handle(&mut world);
+        assert!(forces(&mut world)
+            .iter()
+            .all(|(_, force)| *force == Vec3::ZERO));
+    }
+
+    #[test]
+    fn reset_uses_the_parameters_queued_before() {
+        let mut world = world();
+        push(JsCommand::Reset);
+        push(JsCommand::SetParam("r".to_string(), "30,0,0".to_string()));
+        handle(&mut world);
+
+        let mut stage = SystemStage::single(publish_node_positions);
+        stage.run(&mut world);
+        let positions = node_positions();
+        assert_eq!(positions.len(), 4 * 3 * 3);
+        // Row by row, node 1x2 is the tenth one
+        assert_eq!(positions[9 * 3..10 * 3], [30.0, -60.0, 0.0]);
+
+        let params = world.resource::<Params>();
+        assert_eq!(params.r[1], 30.0 * 2.0_f32.sqrt());
+        assert_eq!(params.r[2], 60.0);
+    }
+}
@@ -0,0 +1,125 @@
+/// Alpha below which a pixel of the mask is treated as empty
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// Opaque pixels of this color are pinned
+pub const PIN_COLOR: [u8; 3] = [255, 0, 0];
+
+/// How far every channel may stray from the pin color, so compressed or anti-aliased pins
+/// still count
+pub const PIN_TOLERANCE: u8 = 48;
+
+/// What a single grid cell of the cloth holds
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Cell {
+    Empty,
+    Cloth,
+    Pin,
+}
+
+/// Shape of the cloth, one cell for every node of the grid
+#[derive(Clone)]
+pub struct ClothMask {
+    pub width: usize,
+    pub height: usize,
+    cells: Vec<Cell>,
+}
+
+impl ClothMask {
+    /// Full rectangle with the top row pinned
+    pub fn rectangle(width: usize, height: usize) -> Self {
+        let cells = (0..height)
+            .flat_map(|y| (0..width).map(move |_| if y == 0 { Cell::Pin } else { Cell::Cloth }))
+            .collect();
+
+        ClothMask {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Loads a PNG where every pixel is a node. Transparent pixels are left out, pixels close
+    /// to `PIN_COLOR` are pinned and any other opaque color is cloth.
+    pub fn from_png(path: &str) -> Result<Self, String> {
+        let image = image::open(path)
+            .map_err(|error| format!("could not load mask {}: {}", path, error))?
+            .to_rgba8();
+
+        Self::from_pixels(
+            image.width() as usize,
+            image.height() as usize,
+            image.pixels().map(|pixel| pixel.0),
+        )
+    }
+
+    /// Builds the mask from RGBA pixels in row order
+    pub fn from_pixels(
+        width: usize,
+        height: usize,
+        pixels: impl Iterator<Item = [u8; 4]>,
+    ) -> Result<Self, String> {
+        let pixels: Vec<[u8; 4]> = pixels.collect();
+        if width == 0 || height == 0 || pixels.len() != width * height {
+            return Err(format!("mask of {}x{} has no valid pixels", width, height));
+        }
+
+        let is_opaque = |pixel: &[u8; 4]| pixel[3] >= ALPHA_THRESHOLD;
+        let is_pin = |pixel: &[u8; 4]| {
+            PIN_COLOR
+                .iter()
+                .zip(pixel)
+                .all(|(pin, channel)| pin.abs_diff(*channel) <= PIN_TOLERANCE)
+        };
+
+        if !pixels.iter().any(is_opaque) {
+            return Err("mask has no opaque pixels".to_string());
+        }
+
+        let cells = pixels
+            .iter()
+            .map(|pixel| {
+                if !is_opaque(pixel) {
+                    Cell::Empty
+                } else if is_pin(pixel) {
+                    Cell::Pin
+                } else {
+                    Cell::Cloth
+                }
+            })
+            .collect();
+
+        Ok(ClothMask {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> Cell {
+        self.cells[y * self.width + x]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask_pins_only_the_pin_color() {
+        let transparent = [0, 0, 0, 0];
+        let cloth = [255, 255, 255, 255];
+        let anti_aliased_pin = [230, 20, 10, 255];
+        let other = [40, 90, 200, 255];
+        let mask = ClothMask::from_pixels(
+            4,
+            1,
+            [transparent, cloth, anti_aliased_pin, other].into_iter(),
+        )
+        .unwrap();
+
+        let cells: Vec<Cell> = (0..4).map(|x| mask.cell(x, 0)).collect();
+        assert_eq!(cells, [Cell::Empty, Cell::Cloth, Cell::Pin, Cell::Cloth]);
+
+        assert!(ClothMask::from_pixels(1, 1, [transparent].into_iter()).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+
+use super::physics::Edge;
+use super::Grid;
+use bevy::prelude::*;
+
+/// Triangle spanned by three nodes of the cloth
+#[derive(Clone, Copy)]
+pub struct Face {
+    pub nodes: [Entity; 3],
+    /// which of the sides `ab`, `bc` and `ca` had an edge when the cloth was created
+    pub sides: [bool; 3],
+}
+
+/// All triangles of the cloth surface, used for aerodynamics
+pub struct Faces(pub Vec<Face>);
+
+/// Set of node pairs which are still connected by an edge
+pub struct Links(HashSet<(Entity, Entity)>);
+
+impl Faces {
+    /// Splits every grid cell into two triangles, triangles with an empty corner are left out.
+    pub fn from_grid(grid: &Grid) -> Self {
+        let mut faces = Vec::new();
+        let mut add_face = |nodes: [Option<Entity>; 3], sides: [bool; 3]| {
+            if let [Some(a), Some(b), Some(c)] = nodes {
+                faces.push(Face {
+                    nodes: [a, b, c],
+                    sides,
+                });
+            }
+        };
+
+        for k in 1..grid.0.len() {
+            for i in 1..grid.0[k].len() {
+                let top_left = grid.0[k - 1][i - 1];
+                let top_right = grid.0[k - 1][i];
+                let bottom_left = grid.0[k][i - 1];
+                let bottom_right = grid.0[k][i];
+
+                // The diagonal of a cell has no edge
+                add_face([top_left, bottom_left, top_right], [true, false, true]);
+                add_face([top_right, bottom_left, bottom_right], [false, true, true]);
+            }
+        }
+
+        Faces(faces)
+    }
+
+    /// Faces of an imported mesh, triangles index into the nodes.
+    pub fn from_triangles(nodes: &[Entity], triangles: &[[usize; 3]]) -> Self {
+        Faces(
+            triangles
+                .iter()
+                .map(|triangle| Face {
+                    nodes: triangle.map(|vertex| nodes[vertex]),
+                    sides: [true; 3],
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Face {
+    /// A face is intact as long as its three nodes are connected through its own sides,
+    /// that is at least two of the sides are still linked. Grid cells have no diagonal
+    /// edge, so a full check of all three sides would never pass.
+    pub fn is_intact(&self, links: &Links) -> bool {
+        let [a, b, c] = self.nodes;
+        let linked_sides = [(a, b), (b, c), (c, a)]
+            .iter()
+            .filter(|(x, y)| links.contains(*x, *y))
+            .count();
+
+        linked_sides >= 2
+    }
+
+    /// Every side which had an edge when the cloth was created is still linked, so a face
+    /// with any cut side counts as torn
+    pub fn is_complete(&self, links: &Links) -> bool {
+        let [a, b, c] = self.nodes;
+        [(a, b), (b, c), (c, a)]
+            .iter()
+            .zip(self.sides)
+            .all(|((x, y), had_edge)| !had_edge || links.contains(*x, *y))
+    }
+}
+
+impl Links {
+    pub fn from_edges<'a>(edges: impl Iterator<Item = &'a Edge>) -> Self {
+        Links(edges.map(|edge| Self::key(edge.a, edge.b)).collect())
+    }
+
+    pub fn contains(&self, a: Entity, b: Entity) -> bool {
+        self.0.contains(&Self::key(a, b))
+    }
+
+    fn key(a: Entity, b: Entity) -> (Entity, Entity) {
+        if a < b {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+}
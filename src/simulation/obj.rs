@@ -0,0 +1,166 @@
+use std::collections::BTreeSet;
+use std::fs;
+
+use bevy::prelude::*;
+
+/// Triangle mesh loaded from an OBJ file, every vertex becomes a node
+#[derive(Clone)]
+pub struct ObjMesh {
+    pub positions: Vec<Vec3>,
+    pub triangles: Vec<[usize; 3]>,
+}
+
+impl ObjMesh {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|error| format!("could not read mesh {}: {}", path, error))?;
+        Self::parse(&source).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    /// Reads vertices and faces, polygons are split into triangle fans. Everything else
+    /// (normals, texture coordinates, groups, materials) is ignored.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut positions = Vec::new();
+        let mut triangles = Vec::new();
+
+        for (number, line) in source.lines().enumerate() {
+            let error = |message: &str| format!("line {}: {}", number + 1, message);
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("v") => {
+                    let coordinates = tokens
+                        .take(3)
+                        .map(|token| token.parse::<f32>())
+                        .collect::<Result<Vec<f32>, _>>()
+                        .map_err(|_| error("invalid vertex coordinate"))?;
+                    if coordinates.len() != 3 {
+                        return Err(error("vertex needs three coordinates"));
+                    }
+                    positions.push(Vec3::new(coordinates[0], coordinates[1], coordinates[2]));
+                }
+                Some("f") => {
+                    // Indices start at one, negative ones count back from the last vertex
+                    let vertices = tokens
+                        .map(|token| {
+                            let index: i64 = token
+                                .split('/')
+                                .next()
+                                .unwrap_or_default()
+                                .parse()
+                                .map_err(|_| error("invalid face index"))?;
+                            let index = if index < 0 {
+                                positions.len() as i64 + index
+                            } else {
+                                index - 1
+                            };
+
+                            if index < 0 || index as usize >= positions.len() {
+                                return Err(error("face index out of range"));
+                            }
+                            Ok(index as usize)
+                        })
+                        .collect::<Result<Vec<usize>, String>>()?;
+
+                    if vertices.len() < 3 {
+                        return Err(error("face needs at least three vertices"));
+                    }
+                    // A repeated vertex would connect a node to itself
+                    if (1..vertices.len()).any(|i| vertices[..i].contains(&vertices[i])) {
+                        return Err(error("face repeats a vertex"));
+                    }
+                    for i in 1..vertices.len() - 1 {
+                        triangles.push([vertices[0], vertices[i], vertices[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if triangles.is_empty() {
+            return Err("mesh has no faces".to_string());
+        }
+
+        Ok(ObjMesh {
+            positions,
+            triangles,
+        })
+    }
+
+    /// Unique vertex pairs connected by a side of a triangle
+    pub fn edges(&self) -> Vec<[usize; 2]> {
+        let mut edges = BTreeSet::new();
+        for &[a, b, c] in self.triangles.iter() {
+            for (x, y) in [(a, b), (b, c), (c, a)] {
+                edges.insert([x.min(y), x.max(y)]);
+            }
+        }
+        edges.into_iter().collect()
+    }
+
+    /// Vertex positions in the world, scaled so the average edge is as long as the given
+    /// rest length and moved so the top left corner is at the origin like the grid
+    pub fn layout(&self, rest_length: f32) -> Vec<Vec3> {
+        let edges = self.edges();
+        let average_length = edges
+            .iter()
+            .map(|[a, b]| self.positions[*a].distance(self.positions[*b]))
+            .sum::<f32>()
+            / edges.len() as f32;
+        let scale = if average_length > f32::EPSILON {
+            rest_length / average_length
+        } else {
+            1.0
+        };
+
+        let min_x = self
+            .positions
+            .iter()
+            .map(|pos| pos.x)
+            .fold(f32::MAX, f32::min);
+        let max_y = self
+            .positions
+            .iter()
+            .map(|pos| pos.y)
+            .fold(f32::MIN, f32::max);
+        let corner = Vec3::new(min_x, max_y, 0.0);
+
+        self.positions
+            .iter()
+            .map(|pos| (*pos - corner) * scale)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n";
+
+    #[test]
+    fn polygons_become_triangle_fans() {
+        let mesh = ObjMesh::parse(&format!("{}vn 0 0 1\nf 1/1/1 2/2/1 3 -1\n", SQUARE)).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.edges(), vec![[0, 1], [0, 2], [0, 3], [1, 2], [2, 3]]);
+    }
+
+    #[test]
+    fn invalid_faces_are_rejected() {
+        for (face, message) in [
+            ("f 1 2", "line 5: face needs at least three vertices"),
+            ("f 1 2 5", "line 5: face index out of range"),
+            ("f 1 x 2", "line 5: invalid face index"),
+            ("f 1 1 2", "line 5: face repeats a vertex"),
+            ("f 1 2 3 -4", "line 5: face repeats a vertex"),
+        ] {
+            let result = ObjMesh::parse(&format!("{}{}\n", SQUARE, face));
+            assert_eq!(result.err().as_deref(), Some(message), "{}", face);
+        }
+        assert_eq!(
+            ObjMesh::parse(SQUARE).err().as_deref(),
+            Some("mesh has no faces")
+        );
+    }
+}
@@ -1,7 +1,33 @@
-use super::util;
+use std::collections::HashMap;
+
+use super::bending::{apply_bending, Bending};
+use super::bounds::apply_bounds;
+use super::damping::damp_edge;
+use super::fragments::Asleep;
+use super::mesh::Links;
+use super::storage::StorageBackend;
+use super::substeps::Substeps;
+use super::tethers::{apply_tethers, Tethers};
 use super::Params;
 use bevy::prelude::*;
-use bevy::sprite::Rect;
+use bevy::tasks::ComputeTaskPool;
+
+/// Number of substeps within a single physics update, unless adapted
+pub const NUM_STEPS: usize = 5;
+
+/// Number of nodes or edges handled by a single task when solving in parallel
+const BATCH_SIZE: usize = 256;
+
+/// How the springs of a color are solved
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum SpringSolver {
+    /// Batches of a color are solved in parallel tasks, colors with a single batch and pools
+    /// with a single thread fall back to the serial loop
+    #[default]
+    Parallel,
+    /// One edge after the other, in the same order of colors
+    Serial,
+}
 
 #[derive(Component)]
 pub struct PreviousPosition(pub Vec3);
@@ -16,11 +42,14 @@ pub struct Mass(pub f32);
 pub struct Edge {
     pub a: Entity,
     pub b: Entity,
+    /// rest length of imported edges, grid edges follow the structural rest length
+    pub rest_length: Option<f32>,
 }
 
-#[derive(Component)]
-pub struct WindWave {
-    pub rect: Rect,
+impl Edge {
+    pub fn rest_length(&self, structural_rest_length: f32) -> f32 {
+        self.rest_length.unwrap_or(structural_rest_length)
+    }
 }
 
 #[derive(Component, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
@@ -32,153 +61,283 @@ pub struct Index {
 #[derive(Component)]
 pub struct Pinned;
 
+/// Nodes moved by the physics, sleeping fragments are left out
+pub type Awake = (With<Index>, Without<Asleep>);
+
+/// Awake nodes with everything the solvers read and write
+pub type SolverNodes<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut PreviousPosition,
+        &'static mut Force,
+        &'static Mass,
+        Option<&'static Pinned>,
+    ),
+    Awake,
+>;
+
+/// Edges grouped by color, edges of the same color share no nodes and can be solved in
+/// parallel. The coloring is greedy, so it takes at most twice the highest number of edges
+/// at a node minus one colors, a few more than the four a grid would strictly need.
+#[derive(Default)]
+pub struct EdgeColoring(pub Vec<Vec<Entity>>);
+
+/// Recolors the edges whenever new ones are created. Removed and sleeping edges are simply
+/// skipped while solving, so they do not require recoloring.
+pub fn color_edges(
+    mut coloring: ResMut<EdgeColoring>,
+    added_edges: Query<(), Added<Edge>>,
+    edges: Query<(Entity, &Edge)>,
+) {
+    if added_edges.is_empty() {
+        return;
+    }
+
+    // Greedy coloring, every node keeps the colors already used by its edges. Meshes can
+    // have nodes with any number of edges, so the colors are not limited to a bit mask.
+    let mut used_colors: HashMap<Entity, Vec<bool>> = HashMap::new();
+    let mut groups: Vec<Vec<Entity>> = Vec::new();
+
+    for (entity, edge) in edges.iter() {
+        let is_used = |node: Entity, color: usize| {
+            used_colors
+                .get(&node)
+                .and_then(|used| used.get(color).copied())
+                .unwrap_or(false)
+        };
+        let color = (0..)
+            .find(|color| !is_used(edge.a, *color) && !is_used(edge.b, *color))
+            .unwrap();
+
+        for node in [edge.a, edge.b] {
+            let used = used_colors.entry(node).or_default();
+            if used.len() <= color {
+                used.resize(color + 1, false);
+            }
+            used[color] = true;
+        }
+
+        if groups.len() <= color {
+            groups.resize_with(color + 1, Vec::new);
+        }
+        groups[color].push(entity);
+    }
+
+    coloring.0 = groups;
+}
+
 pub fn physics_update(
     params: Res<Params>,
-    edges: Query<&Edge>,
-    mut nodes: Query<
-        (
-            &mut Transform,
-            &mut PreviousPosition,
-            &mut Force,
-            &Mass,
-            Option<&Pinned>,
-        ),
-        With<Index>,
-    >,
+    substeps: Res<Substeps>,
+    tethers: Res<Tethers>,
+    bending: Res<Bending>,
+    coloring: Res<EdgeColoring>,
+    edges: Query<&Edge, Without<Asleep>>,
+    mut nodes: SolverNodes,
 ) {
-    let num_steps = 5;
-    let step_dt = params.dt / num_steps as f32;
+    if params.storage != StorageBackend::Ecs || params.paused {
+        return;
+    }
 
-    for _ in 0..num_steps {
+    let step_dt = substeps.dt(params.dt);
+    let links = (params.bending_stiffness > 0.0).then(|| Links::from_edges(edges.iter()));
+
+    for _ in 0..substeps.total() {
         apply_gravity(&params, &mut nodes);
 
         update_nodes(step_dt, &params, &mut nodes);
 
+        apply_edge_damping(step_dt, &params, &edges, &mut nodes);
+
         for _ in 0..3 {
-            apply_spring_forces(step_dt, &params, &edges, &mut nodes);
+            apply_spring_forces(step_dt, &params, &coloring, &edges, &mut nodes);
+        }
+
+        if let Some(links) = links.as_ref() {
+            apply_bending(&params, &bending, links, &mut nodes);
         }
+
+        if params.enable_tethers {
+            apply_tethers(&params, &tethers, &mut nodes);
+        }
+
+        apply_bounds(&params.bounds, &mut nodes);
     }
 }
 
 // This system applies gravity to Nodes without Pinned component
-fn apply_gravity(
-    params: &Res<Params>,
-    nodes: &mut Query<
-        (
-            &mut Transform,
-            &mut PreviousPosition,
-            &mut Force,
-            &Mass,
-            Option<&Pinned>,
-        ),
-        With<Index>,
-    >,
-) {
-    for (_, _, mut force, mass, pinned) in nodes.iter_mut() {
+fn apply_gravity(params: &Res<Params>, nodes: &mut SolverNodes) {
+    let gravity = params.gravity();
+
+    nodes.par_for_each_mut(BATCH_SIZE, |(_, _, mut force, mass, pinned)| {
         if let None = pinned {
-            force.0 += Vec3::new(0.0, -params.g, 0.0) * mass.0;
+            force.0 += gravity * mass.0;
         }
-    }
+    });
 }
 
+/// Solves the springs one color at a time. The edges of a color are split into batches which
+/// are solved in parallel, every task moves the nodes of its edges right away. Spawning the
+/// tasks costs more than it saves on a single batch or thread, those colors are solved
+/// serially. Edges of a color share no nodes, so both give the same result.
 fn apply_spring_forces(
     dt: f32,
     params: &Res<Params>,
-    edges: &Query<&Edge>,
-    nodes: &mut Query<
-        (
-            &mut Transform,
-            &mut PreviousPosition,
-            &mut Force,
-            &Mass,
-            Option<&Pinned>,
-        ),
-        With<Index>,
-    >,
+    coloring: &EdgeColoring,
+    edges: &Query<&Edge, Without<Asleep>>,
+    nodes: &mut SolverNodes,
 ) {
-    for edge in edges.iter() {
-        let [(mut a_pos, _, _, a_mass, a_pinned), (mut b_pos, _, _, b_mass, b_pinned)] =
-            nodes.many_mut([edge.a, edge.b]);
+    let structural_rest_length = params.r[0];
+    let k = params.k[0];
+    let pool = ComputeTaskPool::get();
 
-        let difference = a_pos.translation - b_pos.translation;
-        let distance = difference.length();
-        let tension = params.r[0] - distance;
+    for group in coloring.0.iter() {
+        if params.spring_solver == SpringSolver::Serial
+            || pool.thread_num() <= 1
+            || group.len() <= BATCH_SIZE
+        {
+            for entity in group {
+                let edge = match edges.get(*entity) {
+                    Ok(edge) => edge,
+                    _ => continue,
+                };
+                if let Ok(
+                    [(mut a_pos, _, _, a_mass, a_pinned), (mut b_pos, _, _, b_mass, b_pinned)],
+                ) = nodes.get_many_mut([edge.a, edge.b])
+                {
+                    solve_spring(
+                        dt,
+                        k,
+                        edge.rest_length(structural_rest_length),
+                        (&mut a_pos, a_mass, a_pinned.is_some()),
+                        (&mut b_pos, b_mass, b_pinned.is_some()),
+                    );
+                }
+            }
+            continue;
+        }
 
-        let f = -(params.k[0] * tension);
+        let nodes_ref = &*nodes;
 
-        if let None = a_pinned {
-            a_pos.translation += 0.5 * -((difference / distance) * f / a_mass.0) * dt * dt;
-        }
+        pool.scope(|scope| {
+            for batch in group.chunks(BATCH_SIZE) {
+                scope.spawn(async move {
+                    for entity in batch {
+                        let edge = match edges.get(*entity) {
+                            Ok(edge) if edge.a != edge.b => edge,
+                            _ => continue,
+                        };
 
-        if let None = b_pinned {
-            b_pos.translation += 0.5 * ((difference / distance) * f / b_mass.0) * dt * dt;
-        }
+                        // SAFETY: no two edges of a color share a node and both nodes of an
+                        // edge differ, so every node is borrowed by a single task at a time.
+                        // The exclusive borrow of the query keeps everything else out.
+                        let states = unsafe {
+                            (
+                                nodes_ref.get_unchecked(edge.a),
+                                nodes_ref.get_unchecked(edge.b),
+                            )
+                        };
+                        if let (
+                            Ok((mut a_pos, _, _, a_mass, a_pinned)),
+                            Ok((mut b_pos, _, _, b_mass, b_pinned)),
+                        ) = states
+                        {
+                            solve_spring(
+                                dt,
+                                k,
+                                edge.rest_length(structural_rest_length),
+                                (&mut a_pos, a_mass, a_pinned.is_some()),
+                                (&mut b_pos, b_mass, b_pinned.is_some()),
+                            );
+                        }
+                    }
+                });
+            }
+        });
     }
 }
 
-// Calculates new node position based on Force component
-fn update_nodes(
+/// Moves both free nodes of a spring halfway towards its rest length
+fn solve_spring(
     dt: f32,
-    params: &Res<Params>,
-    nodes: &mut Query<
-        (
-            &mut Transform,
-            &mut PreviousPosition,
-            &mut Force,
-            &Mass,
-            Option<&Pinned>,
-        ),
-        With<Index>,
-    >,
+    k: f32,
+    rest_length: f32,
+    (a_pos, a_mass, a_pinned): (&mut Transform, &Mass, bool),
+    (b_pos, b_mass, b_pinned): (&mut Transform, &Mass, bool),
 ) {
-    for (mut pos, mut prev_pos, mut force, mass, _) in nodes.iter_mut() {
+    let difference = a_pos.translation - b_pos.translation;
+    let distance = difference.length();
+    let tension = rest_length - distance;
+
+    let f = -(k * tension);
+
+    if !a_pinned {
+        a_pos.translation += 0.5 * -((difference / distance) * f / a_mass.0) * dt * dt;
+    }
+
+    if !b_pinned {
+        b_pos.translation += 0.5 * ((difference / distance) * f / b_mass.0) * dt * dt;
+    }
+}
+
+// Calculates new node position based on Force component
+fn update_nodes(dt: f32, params: &Res<Params>, nodes: &mut SolverNodes) {
+    let velocity_factor = params.damping.velocity_factor(params.dampen_factor, dt);
+
+    nodes.par_for_each_mut(BATCH_SIZE, |(mut pos, mut prev_pos, mut force, mass, _)| {
         let a = force.0 / mass.0;
 
         let new_pos =
-            pos.translation + params.dampen_factor * (pos.translation - prev_pos.0) + a * dt * dt;
+            pos.translation + velocity_factor * (pos.translation - prev_pos.0) + a * dt * dt;
         prev_pos.0 = pos.translation;
 
         // New pos
         pos.translation = new_pos;
 
         force.0 = Vec3::ZERO;
-    }
+    });
 }
 
-pub fn apply_wind(
-    windows: Res<Windows>,
-    params: Res<Params>,
-    mut wind_waves: Query<(&mut WindWave, &mut Force), Without<Index>>,
-    mut nodes: Query<(&Transform, &mut Force), (With<Index>, Without<Pinned>)>,
+/// Damps the stretch velocity along every edge, if the damping model asks for it
+fn apply_edge_damping(
+    dt: f32,
+    params: &Res<Params>,
+    edges: &Query<&Edge, Without<Asleep>>,
+    nodes: &mut SolverNodes,
 ) {
-    let dt = params.dt;
+    if !params.damping.damps_edges() {
+        return;
+    }
 
-    let window = util::get_primary_window_size(&windows);
-    for (mut wave, wave_force) in wind_waves.iter_mut() {
-        wave.rect.min.x += wave_force.0.x * dt;
-        wave.rect.max.x += wave_force.0.x * dt;
+    let inverse_mass = |mass: &Mass, pinned: Option<&Pinned>| match pinned {
+        Some(_) => 0.0,
+        None => 1.0 / mass.0,
+    };
 
-        if wave.rect.min.x >= window.x {
-            wave.rect.min.x -= window.x;
-            wave.rect.max.x -= window.x;
-        }
+    for edge in edges.iter() {
+        if let Ok(
+            [(a_pos, mut a_prev_pos, _, a_mass, a_pinned), (b_pos, mut b_prev_pos, _, b_mass, b_pinned)],
+        ) = nodes.get_many_mut([edge.a, edge.b])
+        {
+            let a_inverse_mass = inverse_mass(a_mass, a_pinned);
+            let b_inverse_mass = inverse_mass(b_mass, b_pinned);
 
-        //wave.rect.min.y += wave_force.0.y * dt;
-        //wave.rect.max.y += wave_force.0.y * dt;
-
-        //if wave.rect.min.y >= window.y {
-        //wave.rect.min.y += window.y;
-        //wave.rect.max.y += window.y;
-        //}
-
-        for (pos, mut node_force) in nodes.iter_mut() {
-            if pos.translation.x >= wave.rect.min.x
-                && pos.translation.x <= wave.rect.max.x
-                && pos.translation.y >= wave.rect.min.y
-                && pos.translation.y <= wave.rect.max.y
-            {
-                node_force.0 += wave_force.0;
-            }
+            let rate = params
+                .damping
+                .edge_rate(params.k[0], a_inverse_mass, b_inverse_mass);
+
+            damp_edge(
+                rate,
+                dt,
+                a_pos.translation,
+                &mut a_prev_pos.0,
+                a_inverse_mass,
+                b_pos.translation,
+                &mut b_prev_pos.0,
+                b_inverse_mass,
+            );
         }
     }
 }
@@ -0,0 +1,352 @@
+use std::fs;
+use std::path::Path;
+
+use super::fragments::Fragments;
+use super::physics::{Edge, Index};
+use super::Params;
+use bevy::prelude::*;
+use bevy::sprite::Rect;
+
+/// Directory the takes are written to, relative to the working directory, every take gets
+/// its own numbered directory within
+const RECORD_DIRECTORY: &str = "record";
+
+const BACKGROUND: [u8; 4] = [102, 102, 102, 255];
+const FOREGROUND: [u8; 4] = [255, 255, 255, 255];
+
+#[derive(Clone, Copy)]
+pub struct RecordOptions {
+    /// write every physics update to a numbered PNG
+    pub enabled: bool,
+    pub width: u32,
+    pub height: u32,
+    /// radius of the node circles in pixels, zero to only draw edges
+    pub node_radius: f32,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        RecordOptions {
+            enabled: false,
+            width: 1280,
+            height: 720,
+            node_radius: 0.0,
+        }
+    }
+}
+
+/// State of the recording
+#[derive(Default)]
+pub struct Recorder {
+    /// world space region shown in the frames, fixed by the first recorded frame
+    view: Option<Rect>,
+    /// directory of the current take, picked by its first frame
+    pub directory: Option<String>,
+    /// number of frames written so far
+    pub frames: u64,
+    /// whether the recording was enabled in the last update
+    recording: bool,
+}
+
+impl Recorder {
+    /// Every recording is a new take, with a new view, directory and frame numbers
+    fn follow(&mut self, enabled: bool) {
+        if enabled && !self.recording {
+            self.view = None;
+            self.directory = None;
+            self.frames = 0;
+        }
+        self.recording = enabled;
+    }
+}
+
+/// RGBA image drawn on the CPU, independent of the window and the GPU
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, color: [u8; 4]) -> Self {
+        Canvas {
+            width,
+            height,
+            pixels: color
+                .iter()
+                .copied()
+                .cycle()
+                .take((width * height * 4) as usize)
+                .collect(),
+        }
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, color: [u8; 4]) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+
+        let i = ((y as u32 * self.width + x as u32) * 4) as usize;
+        self.pixels[i..i + 4].copy_from_slice(&color);
+    }
+
+    /// Line between two pixel positions, one pixel wide
+    pub fn line(&mut self, a: Vec2, b: Vec2, color: [u8; 4]) {
+        if !a.is_finite() || !b.is_finite() {
+            return;
+        }
+        let steps = (b - a).abs().max_element().ceil().max(1.0);
+        // Lines far outside of the canvas are not worth walking
+        if steps > 10.0 * (self.width + self.height) as f32 {
+            return;
+        }
+
+        for i in 0..=steps as u32 {
+            let point = a.lerp(b, i as f32 / steps);
+            self.set(point.x.round() as i32, point.y.round() as i32, color);
+        }
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, color: [u8; 4]) {
+        if !center.is_finite() {
+            return;
+        }
+        let extent = radius.ceil() as i32;
+        let (cx, cy) = (center.x.round() as i32, center.y.round() as i32);
+
+        for y in -extent..=extent {
+            for x in -extent..=extent {
+                if ((x * x + y * y) as f32) <= radius * radius {
+                    self.set(cx + x, cy + y, color);
+                }
+            }
+        }
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<(), String> {
+        image::save_buffer(
+            path,
+            &self.pixels,
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )
+        .map_err(|err| err.to_string())
+    }
+}
+
+/// Maps world positions to pixels, keeping the aspect ratio and flipping y
+fn to_pixels(view: Rect, width: u32, height: u32) -> impl Fn(Vec3) -> Vec2 {
+    let size = view.max - view.min;
+    let scale = (width as f32 / size.x).min(height as f32 / size.y);
+    let offset = (Vec2::new(width as f32, height as f32) - size * scale) / 2.0;
+
+    move |pos: Vec3| {
+        let pixel = (pos.truncate() - view.min) * scale + offset;
+        Vec2::new(pixel.x, height as f32 - pixel.y)
+    }
+}
+
+/// First take directory within the root which does not exist yet, so earlier takes are kept
+fn next_take(root: &str) -> String {
+    (1..)
+        .map(|take| format!("{}/take_{:03}", root, take))
+        .find(|directory| !Path::new(directory).exists())
+        .unwrap()
+}
+
+/// Draws every physics update to a numbered PNG. Nothing depends on the window, so the same
+/// run always gives the same frames, also headless.
+pub fn record_frames(
+    params: Res<Params>,
+    mut recorder: ResMut<Recorder>,
+    fragments: Option<Res<Fragments>>,
+    edges: Query<&Edge>,
+    nodes: Query<(Entity, &Transform), With<Index>>,
+) {
+    let options = params.record;
+    recorder.follow(options.enabled);
+    if !options.enabled || params.paused || options.width == 0 || options.height == 0 {
+        return;
+    }
+
+    // Frame the cloth as it is when the recording starts, padded by half of its size
+    let view = *recorder.view.get_or_insert_with(|| {
+        let (min, max) = nodes.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), (_, pos)| {
+                (
+                    min.min(pos.translation.truncate()),
+                    max.max(pos.translation.truncate()),
+                )
+            },
+        );
+        let padding = Vec2::splat(((max - min).max_element() / 2.0).max(1.0));
+        Rect {
+            min: min - padding,
+            max: max + padding,
+        }
+    });
+    let to_pixels = to_pixels(view, options.width, options.height);
+
+    let mut canvas = Canvas::new(options.width, options.height, BACKGROUND);
+    let color_of = |node: Entity| {
+        let fragment = fragments
+            .as_ref()
+            .filter(|_| params.fragments.color)
+            .and_then(|fragments| fragments.of_node(node));
+        match fragment {
+            Some(fragment) => Fragments::color(fragment)
+                .as_rgba_f32()
+                .map(|value| (value * 255.0) as u8),
+            None => FOREGROUND,
+        }
+    };
+
+    for edge in edges.iter() {
+        if let Ok([(_, a_pos), (_, b_pos)]) = nodes.get_many([edge.a, edge.b]) {
+            canvas.line(
+                to_pixels(a_pos.translation),
+                to_pixels(b_pos.translation),
+                color_of(edge.a),
+            );
+        }
+    }
+
+    if options.node_radius > 0.0 {
+        for (entity, pos) in nodes.iter() {
+            canvas.circle(
+                to_pixels(pos.translation),
+                options.node_radius,
+                color_of(entity),
+            );
+        }
+    }
+
+    let directory = recorder
+        .directory
+        .get_or_insert_with(|| next_take(RECORD_DIRECTORY))
+        .clone();
+    let path = format!("{}/frame_{:05}.png", directory, recorder.frames);
+    let result = fs::create_dir_all(&directory)
+        .map_err(|err| err.to_string())
+        .and_then(|_| canvas.save_png(&path));
+    match result {
+        Ok(_) => recorder.frames += 1,
+        Err(err) => eprintln!("could not write {}: {}", path, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    fn painted(canvas: &Canvas) -> Vec<(u32, u32)> {
+        (0..canvas.height)
+            .flat_map(|y| (0..canvas.width).map(move |x| (x, y)))
+            .filter(|(x, y)| {
+                let i = ((y * canvas.width + x) * 4) as usize;
+                canvas.pixels[i..i + 4] == RED
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lines_cover_both_ends() {
+        let mut canvas = Canvas::new(8, 4, BACKGROUND);
+        canvas.line(Vec2::new(1.0, 1.0), Vec2::new(6.0, 1.0), RED);
+        assert_eq!(
+            painted(&canvas),
+            (1..=6).map(|x| (x, 1)).collect::<Vec<_>>()
+        );
+
+        let mut canvas = Canvas::new(8, 4, BACKGROUND);
+        canvas.line(Vec2::new(0.0, 0.0), Vec2::new(3.0, 3.0), RED);
+        assert_eq!(painted(&canvas), vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+    }
+
+    #[test]
+    fn drawing_outside_of_the_canvas_is_clipped() {
+        let mut canvas = Canvas::new(4, 4, BACKGROUND);
+        canvas.line(Vec2::new(-10.0, 2.0), Vec2::new(10.0, 2.0), RED);
+        assert_eq!(painted(&canvas), (0..4).map(|x| (x, 2)).collect::<Vec<_>>());
+
+        // Exploded nodes are far away or not finite at all
+        let mut canvas = Canvas::new(4, 4, BACKGROUND);
+        canvas.line(Vec2::ZERO, Vec2::new(1e9, 0.0), RED);
+        canvas.line(Vec2::ZERO, Vec2::new(f32::NAN, 0.0), RED);
+        canvas.circle(Vec2::new(f32::NAN, 0.0), 2.0, RED);
+        canvas.set(-1, 0, RED);
+        canvas.set(4, 0, RED);
+        assert!(painted(&canvas).is_empty());
+    }
+
+    #[test]
+    fn circles_fill_their_radius() {
+        let mut canvas = Canvas::new(9, 9, BACKGROUND);
+        canvas.circle(Vec2::new(4.0, 4.0), 1.0, RED);
+        assert_eq!(
+            painted(&canvas),
+            vec![(4, 3), (3, 4), (4, 4), (5, 4), (4, 5)]
+        );
+
+        let mut canvas = Canvas::new(9, 9, BACKGROUND);
+        canvas.circle(Vec2::new(4.0, 4.0), 2.0, RED);
+        assert_eq!(painted(&canvas).len(), 13);
+    }
+
+    #[test]
+    fn view_is_centered_and_flipped() {
+        let view = Rect {
+            min: Vec2::new(-10.0, -10.0),
+            max: Vec2::new(10.0, 10.0),
+        };
+        // The square view fits the height of the wide image
+        let to_pixels = to_pixels(view, 200, 100);
+        assert_eq!(to_pixels(Vec3::ZERO), Vec2::new(100.0, 50.0));
+        assert_eq!(to_pixels(Vec3::new(-10.0, 10.0, 5.0)), Vec2::new(50.0, 0.0));
+        assert_eq!(
+            to_pixels(Vec3::new(10.0, -10.0, 0.0)),
+            Vec2::new(150.0, 100.0)
+        );
+    }
+
+    #[test]
+    fn recording_again_starts_over() {
+        let mut recorder = Recorder::default();
+        recorder.follow(true);
+        recorder.view = Some(Rect {
+            min: Vec2::ZERO,
+            max: Vec2::ONE,
+        });
+        recorder.frames = 5;
+
+        // Still the same recording
+        recorder.follow(true);
+        assert!(recorder.view.is_some());
+        assert_eq!(recorder.frames, 5);
+
+        recorder.directory = Some("record/take_001".to_string());
+
+        recorder.follow(false);
+        recorder.follow(true);
+        assert!(recorder.view.is_none());
+        assert!(recorder.directory.is_none());
+        assert_eq!(recorder.frames, 0);
+    }
+
+    #[test]
+    fn takes_do_not_overwrite_earlier_ones() {
+        let root = std::env::temp_dir().join(format!("cloth_record_{}", std::process::id()));
+        let root = root.to_str().unwrap();
+        assert_eq!(next_take(root), format!("{}/take_001", root));
+
+        fs::create_dir_all(format!("{}/take_001", root)).unwrap();
+        fs::create_dir_all(format!("{}/take_002", root)).unwrap();
+        assert_eq!(next_take(root), format!("{}/take_003", root));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
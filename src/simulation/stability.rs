@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use super::physics::{Awake, Edge, PreviousPosition};
+use super::substeps::Substeps;
+use super::Params;
+use bevy::prelude::*;
+
+/// Number of stable physics updates between two snapshots
+const SNAPSHOT_INTERVAL: u64 = 30;
+/// Number of stable physics updates before halved substeps are doubled again
+const RESTORE_INTERVAL: u64 = 300;
+/// Rollbacks never split a substep into more than this many
+const MAX_REFINEMENT: usize = 16;
+
+/// What to do once the simulation explodes
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum ExplosionResponse {
+    /// Pause the simulation
+    #[default]
+    Pause,
+    /// Limit the velocity of the offending nodes, broken nodes are restored from the snapshot
+    ClampVelocities,
+    /// Restore the last good snapshot and halve the length of the substeps, which is doubled
+    /// back once the cloth is stable again. The timestep of the physics update stays the same.
+    Rollback,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct StabilityOptions {
+    pub enabled: bool,
+    /// nodes moving faster than this are considered exploding
+    pub max_velocity: f32,
+    pub response: ExplosionResponse,
+}
+
+/// Last known good state of all nodes
+#[derive(Default)]
+pub struct Snapshot {
+    nodes: HashMap<Entity, (Vec3, Vec3)>,
+    /// refinement of the substeps the previous positions were taken with
+    refinement: usize,
+    ticks: u64,
+    /// stable physics updates since the last rollback
+    stable_ticks: u64,
+}
+
+/// Warning about the last explosion, shown in the UI until dismissed
+#[derive(Default)]
+pub struct StabilityWarning(pub Option<String>);
+
+/// Detects NaN positions or velocities beyond the limit and responds to them
+pub fn check_stability(
+    mut params: ResMut<Params>,
+    mut substeps: ResMut<Substeps>,
+    mut snapshot: ResMut<Snapshot>,
+    mut warning: ResMut<StabilityWarning>,
+    added_edges: Query<(), Added<Edge>>,
+    mut nodes: Query<(Entity, &mut Transform, &mut PreviousPosition), Awake>,
+) {
+    let options = params.stability;
+    if !options.enabled || params.paused {
+        return;
+    }
+
+    let max_step = options.max_velocity * substeps.dt(params.dt);
+    let is_broken = |pos: Vec3| !pos.is_finite();
+    let is_exploding = |pos: Vec3, prev_pos: Vec3| pos.distance(prev_pos) > max_step;
+
+    let unstable = nodes.iter().filter(|(_, pos, prev_pos)| {
+        is_broken(pos.translation)
+            || is_broken(prev_pos.0)
+            || is_exploding(pos.translation, prev_pos.0)
+    });
+    let unstable_count = unstable.count();
+
+    if unstable_count == 0 {
+        snapshot.stable_ticks += 1;
+        if substeps.refinement > 1 {
+            // Reset starts over with the configured substeps, otherwise they are doubled back
+            // step by step while the cloth stays stable
+            let refinement = if !added_edges.is_empty() {
+                1
+            } else if snapshot.stable_ticks.is_multiple_of(RESTORE_INTERVAL) {
+                substeps.refinement / 2
+            } else {
+                substeps.refinement
+            };
+
+            if refinement != substeps.refinement {
+                // Keep the velocities, like the rollback does when halving
+                let scale = substeps.refinement as f32 / refinement as f32;
+                for (_, pos, mut prev_pos) in nodes.iter_mut() {
+                    prev_pos.0 = pos.translation - (pos.translation - prev_pos.0) * scale;
+                }
+                substeps.refinement = refinement;
+            }
+        }
+
+        // Reset spawns new edges, older snapshots are no longer valid
+        snapshot.ticks += 1;
+        if snapshot.nodes.is_empty()
+            || !added_edges.is_empty()
+            || snapshot.ticks.is_multiple_of(SNAPSHOT_INTERVAL)
+        {
+            snapshot.nodes = nodes
+                .iter()
+                .map(|(entity, pos, prev_pos)| (entity, (pos.translation, prev_pos.0)))
+                .collect();
+            snapshot.refinement = substeps.refinement;
+        }
+        return;
+    }
+
+    let message = match options.response {
+        ExplosionResponse::Pause => {
+            params.paused = true;
+            format!("{} nodes exploded, simulation paused", unstable_count)
+        }
+        ExplosionResponse::ClampVelocities => {
+            for (entity, mut pos, mut prev_pos) in nodes.iter_mut() {
+                if is_broken(pos.translation) || is_broken(prev_pos.0) {
+                    if let Some((good_pos, good_prev_pos)) = snapshot.nodes.get(&entity) {
+                        let scale = snapshot.refinement as f32 / substeps.refinement as f32;
+                        pos.translation = *good_pos;
+                        prev_pos.0 = *good_pos - (*good_pos - *good_prev_pos) * scale;
+                    }
+                } else if is_exploding(pos.translation, prev_pos.0) {
+                    let velocity = (pos.translation - prev_pos.0).clamp_length_max(max_step);
+                    prev_pos.0 = pos.translation - velocity;
+                }
+            }
+            format!("{} nodes exploded, velocities clamped", unstable_count)
+        }
+        ExplosionResponse::Rollback => {
+            let refinement = (substeps.refinement * 2).min(MAX_REFINEMENT);
+
+            // A shorter substep shortens the distance travelled in it by as much. The snapshot
+            // may be older than the last restore, so scale from its own refinement.
+            let scale = snapshot.refinement as f32 / refinement as f32;
+            for (entity, mut pos, mut prev_pos) in nodes.iter_mut() {
+                if let Some((good_pos, good_prev_pos)) = snapshot.nodes.get(&entity) {
+                    pos.translation = *good_pos;
+                    prev_pos.0 = *good_pos - (*good_pos - *good_prev_pos) * scale;
+                }
+            }
+            substeps.refinement = refinement;
+            snapshot.stable_ticks = 0;
+            format!(
+                "{} nodes exploded, rolled back and reduced the substep to {:.5} s",
+                unstable_count,
+                substeps.dt(params.dt)
+            )
+        }
+    };
+
+    warn!("{}", message);
+    warning.0 = Some(message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::physics::Index;
+
+    #[test]
+    fn rollback_keeps_velocities_of_snapshot_taken_before_restore() {
+        let mut world = World::new();
+        world.insert_resource(Params {
+            dt: 0.02,
+            stability: StabilityOptions {
+                enabled: true,
+                max_velocity: 1000.0,
+                response: ExplosionResponse::Rollback,
+            },
+            ..Default::default()
+        });
+        world.init_resource::<Substeps>();
+        world.resource_mut::<Substeps>().refinement = 4;
+        world.init_resource::<Snapshot>();
+        world.init_resource::<StabilityWarning>();
+
+        let pos = Vec3::new(10.0, 20.0, 0.0);
+        let step = Vec3::new(0.001, 0.0, 0.0);
+        let node = world
+            .spawn()
+            .insert_bundle((
+                Index { x: 0, y: 0 },
+                Transform::from_translation(pos),
+                PreviousPosition(pos - step),
+            ))
+            .id();
+
+        let mut stage = SystemStage::single(check_stability);
+
+        // The snapshot is taken while the substeps are split in 4
+        stage.run(&mut world);
+        assert_eq!(world.resource::<Snapshot>().refinement, 4);
+
+        // Stable long enough to restore half of the refinement
+        world.resource_mut::<Snapshot>().stable_ticks = RESTORE_INTERVAL - 1;
+        stage.run(&mut world);
+        assert_eq!(world.resource::<Substeps>().refinement, 2);
+
+        // Explode and roll back to the snapshot, refined back to 4
+        world.get_mut::<Transform>(node).unwrap().translation.x += 1000.0;
+        stage.run(&mut world);
+        assert_eq!(world.resource::<Substeps>().refinement, 4);
+
+        let restored = world.get::<Transform>(node).unwrap().translation;
+        let restored_prev = world.get::<PreviousPosition>(node).unwrap().0;
+        assert_eq!(restored, pos);
+        assert!(
+            (restored - restored_prev).distance(step) < 1e-6,
+            "rolled back with a step of {}",
+            restored - restored_prev
+        );
+    }
+}
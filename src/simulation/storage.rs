@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+
+use super::bending::{bending_corrections, Bending};
+use super::damping::damp_edge;
+use super::fragments::Asleep;
+use super::mesh::Links;
+use super::physics::{Awake, Edge, Force, Mass, Pinned, PreviousPosition};
+use super::substeps::Substeps;
+use super::tethers::{limit_distance, Tether, Tethers};
+use super::topology::Topology;
+use super::Params;
+use bevy::prelude::*;
+
+/// Where the physics keeps the cloth while solving
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum StorageBackend {
+    /// Solve directly on the node entities
+    #[default]
+    Ecs,
+    /// Solve on contiguous arrays, the entities only mirror them for rendering and the
+    /// systems around the physics
+    Contiguous,
+}
+
+/// Awake nodes with their entity, to map them to their slot
+type StorageNodes<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Transform,
+        &'static mut PreviousPosition,
+        &'static mut Force,
+        &'static Mass,
+        Option<&'static Pinned>,
+    ),
+    Awake,
+>;
+
+/// Awake nodes changed outside of the physics, like by the mouse, the wind or a rollback
+type ChangedNodes<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static PreviousPosition,
+        &'static Force,
+        &'static Mass,
+    ),
+    (
+        Awake,
+        Or<(
+            Changed<Transform>,
+            Changed<PreviousPosition>,
+            Changed<Force>,
+            Changed<Mass>,
+        )>,
+    ),
+>;
+
+/// Cloth state in contiguous arrays, every node has its own slot. While the contiguous
+/// backend is selected the storage holds the state of the cloth between updates.
+#[derive(Default)]
+pub struct ClothStorage {
+    pub entities: Vec<Entity>,
+    pub positions: Vec<Vec3>,
+    pub previous_positions: Vec<Vec3>,
+    /// zero for pinned nodes
+    pub inverse_masses: Vec<f32>,
+    pub forces: Vec<Vec3>,
+    /// slots of the nodes connected by an edge
+    pub constraints: Vec<[usize; 2]>,
+    /// rest length of every constraint, none for the structural rest length
+    pub rest_lengths: Vec<Option<f32>>,
+    /// slots of the node and its anchor
+    pub tethers: Vec<(usize, usize, Tether)>,
+    /// slots of the face pairs which resist bending, with their rest angle
+    pub bending: Vec<([usize; 4], f32)>,
+    slots: HashMap<Entity, usize>,
+    /// topology generation the storage was built for
+    generation: u64,
+    /// the storage holds the state of the cloth, false until it is built and whenever the
+    /// ECS backend takes over
+    current: bool,
+}
+
+impl ClothStorage {
+    /// Copies nodes and edges from the entities
+    fn rebuild(
+        &mut self,
+        tethers: &Tethers,
+        bending: &Bending,
+        edges: &Query<&Edge, Without<Asleep>>,
+        nodes: &StorageNodes,
+    ) {
+        *self = ClothStorage::default();
+
+        for (entity, pos, prev_pos, force, mass, pinned) in nodes.iter() {
+            self.slots.insert(entity, self.entities.len());
+            self.entities.push(entity);
+            self.positions.push(pos.translation);
+            self.previous_positions.push(prev_pos.0);
+            self.forces.push(force.0);
+            self.inverse_masses.push(inverse_mass(mass, pinned));
+        }
+
+        for edge in edges.iter() {
+            if let (Some(&a), Some(&b)) = (self.slots.get(&edge.a), self.slots.get(&edge.b)) {
+                self.constraints.push([a, b]);
+                self.rest_lengths.push(edge.rest_length);
+            }
+        }
+
+        for tether in tethers.tethers.iter() {
+            if let (Some(&node), Some(&anchor)) =
+                (self.slots.get(&tether.node), self.slots.get(&tether.anchor))
+            {
+                self.tethers.push((node, anchor, *tether));
+            }
+        }
+
+        // Torn face pairs are left out, tearing removes edges which forces a rebuild
+        let links = Links::from_edges(edges.iter());
+        for constraint in bending.0.iter() {
+            if !constraint.faces.iter().all(|face| face.is_intact(&links)) {
+                continue;
+            }
+
+            let slots: Vec<usize> = constraint
+                .nodes
+                .iter()
+                .filter_map(|node| self.slots.get(node).copied())
+                .collect();
+            if let [a, b, c, d] = slots[..] {
+                self.bending.push(([a, b, c, d], constraint.rest_angle));
+            }
+        }
+
+        self.current = true;
+    }
+
+    /// Damps the stretch velocity along every edge, if the damping model asks for it
+    fn damp_edges(&mut self, params: &Params, dt: f32) {
+        if !params.damping.damps_edges() {
+            return;
+        }
+
+        for &[a, b] in self.constraints.iter() {
+            let (a_inverse_mass, b_inverse_mass) = (self.inverse_masses[a], self.inverse_masses[b]);
+            let rate = params
+                .damping
+                .edge_rate(params.k[0], a_inverse_mass, b_inverse_mass);
+
+            let (a_pos, b_pos) = (self.positions[a], self.positions[b]);
+            let (mut a_prev_pos, mut b_prev_pos) =
+                (self.previous_positions[a], self.previous_positions[b]);
+            damp_edge(
+                rate,
+                dt,
+                a_pos,
+                &mut a_prev_pos,
+                a_inverse_mass,
+                b_pos,
+                &mut b_prev_pos,
+                b_inverse_mass,
+            );
+            self.previous_positions[a] = a_prev_pos;
+            self.previous_positions[b] = b_prev_pos;
+        }
+    }
+
+    /// Advances the cloth by a single physics update, same as the ECS solver
+    pub fn step(&mut self, params: &Params, substeps: &Substeps) {
+        let dt = substeps.dt(params.dt);
+        let gravity = params.gravity();
+        let planes = params.bounds.planes();
+        let velocity_factor = params.damping.velocity_factor(params.dampen_factor, dt);
+
+        for _ in 0..substeps.total() {
+            for i in 0..self.positions.len() {
+                if self.inverse_masses[i] == 0.0 {
+                    continue;
+                }
+
+                let a = self.forces[i] * self.inverse_masses[i] + gravity;
+                let pos = self.positions[i];
+
+                self.positions[i] =
+                    pos + velocity_factor * (pos - self.previous_positions[i]) + a * dt * dt;
+                self.previous_positions[i] = pos;
+                self.forces[i] = Vec3::ZERO;
+            }
+
+            self.damp_edges(params, dt);
+
+            for _ in 0..3 {
+                for (&[a, b], rest_length) in self.constraints.iter().zip(&self.rest_lengths) {
+                    let difference = self.positions[a] - self.positions[b];
+                    let distance = difference.length();
+                    let tension = rest_length.unwrap_or(params.r[0]) - distance;
+
+                    let f = -(params.k[0] * tension);
+                    let correction = 0.5 * (difference / distance) * f * dt * dt;
+
+                    self.positions[a] -= correction * self.inverse_masses[a];
+                    self.positions[b] += correction * self.inverse_masses[b];
+                }
+            }
+
+            if params.bending_stiffness > 0.0 {
+                for &(slots, rest_angle) in self.bending.iter() {
+                    let corrections = bending_corrections(
+                        slots.map(|slot| self.positions[slot]),
+                        slots.map(|slot| self.inverse_masses[slot]),
+                        rest_angle,
+                        params.bending_stiffness,
+                    );
+                    for (slot, correction) in slots.into_iter().zip(corrections) {
+                        self.positions[slot] += correction;
+                    }
+                }
+            }
+
+            if params.enable_tethers {
+                for &(node, anchor, tether) in self.tethers.iter() {
+                    if self.inverse_masses[node] != 0.0 {
+                        self.positions[node] = limit_distance(
+                            self.positions[anchor],
+                            self.positions[node],
+                            tether.max_length(params.r[0]),
+                        );
+                    }
+                }
+            }
+
+            if !planes.is_empty() {
+                for i in 0..self.positions.len() {
+                    if self.inverse_masses[i] != 0.0 {
+                        params.bounds.collide(
+                            &planes,
+                            &mut self.positions[i],
+                            &mut self.previous_positions[i],
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn inverse_mass(mass: &Mass, pinned: Option<&Pinned>) -> f32 {
+    match pinned {
+        Some(_) => 0.0,
+        None => 1.0 / mass.0,
+    }
+}
+
+/// Solves the physics on the contiguous storage, which is only rebuilt from the entities
+/// when the topology changes. Other changes to the entities, like forces or a rollback, are
+/// picked up by change detection. The entities get the result of every update, so they can
+/// be rendered and checked by the systems after the physics.
+#[allow(clippy::too_many_arguments)]
+pub fn physics_update_contiguous(
+    params: Res<Params>,
+    substeps: Res<Substeps>,
+    tethers: Res<Tethers>,
+    bending: Res<Bending>,
+    mut storage: ResMut<ClothStorage>,
+    topology: Res<Topology>,
+    edges: Query<&Edge, Without<Asleep>>,
+    mut nodes: ParamSet<(StorageNodes, ChangedNodes)>,
+) {
+    if params.storage != StorageBackend::Contiguous {
+        if storage.current {
+            storage.current = false;
+        }
+        return;
+    }
+
+    let storage = &mut *storage;
+
+    // Changes are picked up while paused too, since they are only reported once
+    if !storage.current
+        || storage.generation != topology.generation
+        || tethers.is_changed()
+        || bending.is_changed()
+    {
+        storage.rebuild(&tethers, &bending, &edges, &nodes.p0());
+        storage.generation = topology.generation;
+    } else {
+        for (entity, pos, prev_pos, force, mass) in nodes.p1().iter() {
+            if let Some(&slot) = storage.slots.get(&entity) {
+                storage.positions[slot] = pos.translation;
+                storage.previous_positions[slot] = prev_pos.0;
+                storage.forces[slot] = force.0;
+                if storage.inverse_masses[slot] != 0.0 {
+                    storage.inverse_masses[slot] = 1.0 / mass.0;
+                }
+            }
+        }
+    }
+
+    if params.paused {
+        return;
+    }
+
+    storage.step(&params, &substeps);
+
+    // The query visits the nodes in the order of the slots until the topology changes, which
+    // rebuilds the storage
+    for (i, (entity, mut pos, mut prev_pos, mut force, _, _)) in nodes.p0().iter_mut().enumerate() {
+        let slot = match storage.entities.get(i) {
+            Some(slotted) if *slotted == entity => i,
+            _ => match storage.slots.get(&entity) {
+                Some(&slot) => slot,
+                None => continue,
+            },
+        };
+
+        pos.translation = storage.positions[slot];
+        prev_pos.0 = storage.previous_positions[slot];
+        // The storage consumed the forces
+        if force.0 != Vec3::ZERO {
+            force.0 = Vec3::ZERO;
+        }
+    }
+}
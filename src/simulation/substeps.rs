@@ -0,0 +1,114 @@
+use super::fragments::Asleep;
+use super::physics::{Awake, Edge, PreviousPosition, NUM_STEPS};
+use super::Params;
+use bevy::prelude::*;
+
+/// Number of calm physics updates before a substep is removed
+const CALM_TICKS: u32 = 30;
+
+/// Bounds for the adaptive substep controller
+#[derive(Default, Clone, Copy)]
+pub struct AdaptiveSteps {
+    pub enabled: bool,
+    pub min_steps: usize,
+    pub max_steps: usize,
+    /// relative stretch of an edge above which substeps are added
+    pub max_strain: f32,
+    /// node velocity above which substeps are added
+    pub max_velocity: f32,
+}
+
+/// Number of substeps the physics update is split into
+pub struct Substeps {
+    pub count: usize,
+    /// every substep is split further into this many after a rollback of an exploding cloth
+    pub refinement: usize,
+    /// consecutive physics updates well within the bounds
+    calm_ticks: u32,
+}
+
+impl Default for Substeps {
+    fn default() -> Self {
+        Substeps {
+            count: NUM_STEPS,
+            refinement: 1,
+            calm_ticks: 0,
+        }
+    }
+}
+
+impl Substeps {
+    /// Number of substeps actually solved
+    pub fn total(&self) -> usize {
+        self.count * self.refinement
+    }
+
+    /// Length of a single substep
+    pub fn dt(&self, dt: f32) -> f32 {
+        dt / self.total() as f32
+    }
+}
+
+/// Adds substeps when the strain or velocity exceed the bounds and removes them once the
+/// cloth stays calm. The decision only depends on the cloth state, so runs are repeatable.
+pub fn adapt_substeps(
+    params: Res<Params>,
+    mut substeps: ResMut<Substeps>,
+    edges: Query<&Edge, Without<Asleep>>,
+    mut nodes: Query<(&Transform, &mut PreviousPosition), Awake>,
+) {
+    if params.paused {
+        return;
+    }
+
+    let options = params.adaptive_steps;
+    let count = if options.enabled {
+        let min_steps = options.min_steps.max(1);
+        let max_steps = options.max_steps.max(min_steps);
+
+        let step_dt = substeps.dt(params.dt);
+        let max_velocity = nodes
+            .iter()
+            .map(|(pos, prev_pos)| pos.translation.distance(prev_pos.0) / step_dt)
+            .fold(0.0, f32::max);
+        let max_strain = edges
+            .iter()
+            .filter_map(|edge| Some((edge, nodes.get_many([edge.a, edge.b]).ok()?)))
+            .map(|(edge, [(a_pos, _), (b_pos, _)])| {
+                let rest_length = edge.rest_length(params.r[0]);
+                (a_pos.translation.distance(b_pos.translation) - rest_length).abs() / rest_length
+            })
+            .fold(0.0, f32::max);
+
+        let count = if max_strain > options.max_strain || max_velocity > options.max_velocity {
+            substeps.calm_ticks = 0;
+            substeps.count + 1
+        } else if max_strain < options.max_strain / 2.0 && max_velocity < options.max_velocity / 2.0
+        {
+            substeps.calm_ticks += 1;
+            if substeps.calm_ticks >= CALM_TICKS {
+                substeps.calm_ticks = 0;
+                substeps.count - 1
+            } else {
+                substeps.count
+            }
+        } else {
+            substeps.calm_ticks = 0;
+            substeps.count
+        };
+        count.clamp(min_steps, max_steps)
+    } else {
+        NUM_STEPS
+    };
+
+    if count == substeps.count {
+        return;
+    }
+
+    // Verlet keeps the velocity as distance per substep, rescale it to the new substep
+    let scale = substeps.count as f32 / count as f32;
+    for (pos, mut prev_pos) in nodes.iter_mut() {
+        prev_pos.0 = pos.translation - (pos.translation - prev_pos.0) * scale;
+    }
+    substeps.count = count;
+}
@@ -0,0 +1,122 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::physics::{Edge, Index, Pinned, SolverNodes};
+use super::topology::Topology;
+use super::Params;
+use bevy::prelude::*;
+
+/// Long-range attachment of a node to its nearest pinned node
+#[derive(Clone, Copy)]
+pub struct Tether {
+    pub node: Entity,
+    pub anchor: Entity,
+    /// number of grid edges on the shortest path between the two
+    pub hops: usize,
+    /// summed rest length of the imported edges on the path
+    pub length: f32,
+}
+
+impl Tether {
+    /// Rest length of the path, grid edges follow the structural rest length
+    pub fn max_length(&self, structural_rest_length: f32) -> f32 {
+        self.length + self.hops as f32 * structural_rest_length
+    }
+}
+
+#[derive(Default)]
+pub struct Tethers {
+    pub tethers: Vec<Tether>,
+    /// topology generation the tethers were computed for
+    generation: u64,
+}
+
+/// Recomputes the tethers whenever the topology changes, since a cut or a removed pin
+/// changes the shortest paths to the pins
+pub fn update_tethers(
+    params: Res<Params>,
+    topology: Res<Topology>,
+    mut tethers: ResMut<Tethers>,
+    edges: Query<&Edge>,
+    pinned: Query<Entity, (With<Index>, With<Pinned>)>,
+) {
+    if tethers.generation == topology.generation {
+        return;
+    }
+
+    let mut neighbours: HashMap<Entity, Vec<(Entity, Option<f32>)>> = HashMap::new();
+    for edge in edges.iter() {
+        neighbours
+            .entry(edge.a)
+            .or_default()
+            .push((edge.b, edge.rest_length));
+        neighbours
+            .entry(edge.b)
+            .or_default()
+            .push((edge.a, edge.rest_length));
+    }
+
+    // Shortest paths from all pins at once. Costs are positive, so their bit patterns are
+    // ordered the same way as the floats.
+    let cost = |hops: usize, length: f32| length + hops as f32 * params.r[0];
+    let mut anchors: HashMap<Entity, (Entity, usize, f32)> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    for pin in pinned.iter() {
+        anchors.insert(pin, (pin, 0, 0.0));
+        queue.push(Reverse((0, pin)));
+    }
+
+    while let Some(Reverse((bits, node))) = queue.pop() {
+        let (anchor, hops, length) = anchors[&node];
+        if bits > cost(hops, length).to_bits() {
+            continue;
+        }
+
+        for (neighbour, rest_length) in neighbours.get(&node).into_iter().flatten() {
+            let path = match rest_length {
+                Some(rest_length) => (hops, length + rest_length),
+                None => (hops + 1, length),
+            };
+            let better = anchors
+                .get(neighbour)
+                .is_none_or(|(_, hops, length)| cost(path.0, path.1) < cost(*hops, *length));
+
+            if better {
+                anchors.insert(*neighbour, (anchor, path.0, path.1));
+                queue.push(Reverse((cost(path.0, path.1).to_bits(), *neighbour)));
+            }
+        }
+    }
+
+    tethers.generation = topology.generation;
+    tethers.tethers = anchors
+        .into_iter()
+        .filter(|(node, (anchor, _, _))| node != anchor)
+        .map(|(node, (anchor, hops, length))| Tether {
+            node,
+            anchor,
+            hops,
+            length,
+        })
+        .collect();
+}
+
+/// Pulls nodes which are further from their anchor than the rest path back towards it
+pub fn apply_tethers(params: &Res<Params>, tethers: &Tethers, nodes: &mut SolverNodes) {
+    for tether in tethers.tethers.iter() {
+        let anchor = match nodes.get(tether.anchor) {
+            Ok((pos, _, _, _, _)) => pos.translation,
+            Err(_) => continue,
+        };
+
+        if let Ok((mut pos, _, _, _, None)) = nodes.get_mut(tether.node) {
+            let max_length = tether.max_length(params.r[0]);
+            pos.translation = limit_distance(anchor, pos.translation, max_length);
+        }
+    }
+}
+
+/// Moves the point to be at most `max_length` away from the anchor
+pub fn limit_distance(anchor: Vec3, point: Vec3, max_length: f32) -> Vec3 {
+    anchor + (point - anchor).clamp_length_max(max_length)
+}
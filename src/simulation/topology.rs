@@ -0,0 +1,118 @@
+use std::collections::HashSet;
+
+use super::fragments::Asleep;
+use super::physics::{Edge, Index, Pinned};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+/// Counts the changes of the cloth topology. Whatever is derived from it remembers the
+/// generation it was built for and is rebuilt once the generation moves on.
+#[derive(Default)]
+pub struct Topology {
+    pub generation: u64,
+    /// changes seen by the last run of the tracker
+    counted: HashSet<(usize, Entity)>,
+}
+
+/// Nodes, edges and pins added or removed, and fragments put to sleep or woken up
+#[derive(SystemParam)]
+pub struct TopologyChanges<'w, 's> {
+    added_nodes: Query<'w, 's, Entity, Added<Index>>,
+    added_edges: Query<'w, 's, Entity, Added<Edge>>,
+    added_pins: Query<'w, 's, Entity, Added<Pinned>>,
+    asleep: Query<'w, 's, Entity, Added<Asleep>>,
+    removed_nodes: RemovedComponents<'w, Index>,
+    removed_edges: RemovedComponents<'w, Edge>,
+    removed_pins: RemovedComponents<'w, Pinned>,
+    woken: RemovedComponents<'w, Asleep>,
+}
+
+impl<'w, 's> TopologyChanges<'w, 's> {
+    /// Changes since the last run of the system, every kind of change is numbered
+    fn changes(&self) -> HashSet<(usize, Entity)> {
+        let added = [
+            self.added_nodes.iter().collect::<Vec<_>>(),
+            self.added_edges.iter().collect(),
+            self.added_pins.iter().collect(),
+            self.asleep.iter().collect(),
+        ];
+        let removed = [
+            self.removed_nodes.iter().collect::<Vec<_>>(),
+            self.removed_edges.iter().collect(),
+            self.removed_pins.iter().collect(),
+            self.woken.iter().collect(),
+        ];
+
+        added
+            .into_iter()
+            .chain(removed)
+            .enumerate()
+            .flat_map(|(kind, entities)| entities.into_iter().map(move |entity| (kind, entity)))
+            .collect()
+    }
+}
+
+/// Runs at the start of every physics update, which sees the changes made since the one
+/// before, and once more in the post update stage, since removals are only reported until the
+/// end of the frame and many frames have no physics update. Both runs see the changes made
+/// in between, so only changes the other run has not seen count as new ones.
+pub fn track_topology(mut topology: ResMut<Topology>, changes: TopologyChanges) {
+    let changed = changes.changes();
+    if changed
+        .iter()
+        .any(|change| !topology.counted.contains(change))
+    {
+        topology.generation += 1;
+    }
+    topology.counted = changed;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_runs_count_a_change_once() {
+        let mut world = World::new();
+        world.init_resource::<Topology>();
+        let node = world.spawn().insert(Index { x: 0, y: 0 }).id();
+
+        // Like the physics stage and the post update stage of a frame
+        let mut physics = SystemStage::single(track_topology);
+        let mut post_update = SystemStage::single(track_topology);
+        let generation = |world: &World| world.resource::<Topology>().generation;
+
+        physics.run(&mut world);
+        post_update.run(&mut world);
+        assert_eq!(generation(&world), 1);
+
+        // Catch-up updates of the same frame see the removal again
+        world.despawn(node);
+        physics.run(&mut world);
+        physics.run(&mut world);
+        post_update.run(&mut world);
+        assert_eq!(generation(&world), 2);
+
+        // A removal in a frame without physics update is counted after it
+        world.clear_trackers();
+        let edge = world
+            .spawn()
+            .insert(Edge {
+                a: node,
+                b: node,
+                rest_length: None,
+            })
+            .id();
+        post_update.run(&mut world);
+        assert_eq!(generation(&world), 3);
+        world.clear_trackers();
+        world.despawn(edge);
+        post_update.run(&mut world);
+        assert_eq!(generation(&world), 4);
+
+        world.clear_trackers();
+        physics.run(&mut world);
+        post_update.run(&mut world);
+        assert_eq!(generation(&world), 4);
+    }
+}
@@ -1,41 +1,236 @@
+use std::ops::RangeInclusive;
+
 use bevy::ecs::schedule::ShouldRun;
 use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy_egui::egui::plot::{Legend, Line, Plot, Value, Values};
 use bevy_egui::{egui, EguiContext};
 
-use super::physics::{Edge, Force, Index, Pinned, PreviousPosition};
-use super::{Grid, Params};
+use super::bindings::{Action, Binding, BindingInput, Bindings};
+use super::damping::DampingModel;
+use super::diagnostics::{EnergyHistory, EnergySample};
+use super::export::{ExportOptions, Exporter};
+use super::fragments::DetachedAction;
+use super::gestures::{Finger, Gesture, TouchGestures};
+use super::mesh::Faces;
+use super::physics::{Edge, Force, Index, Pinned, SpringSolver};
+use super::recording::{RecordOptions, Recorder};
+use super::stability::{ExplosionResponse, StabilityWarning};
+use super::storage::StorageBackend;
+use super::substeps::Substeps;
+use super::wind::{WindModel, WindSource};
+use super::{ClothMask, Grid, ObjMesh, Params, ResetNodes};
 use bevy::prelude::*;
 use bevy::render::camera::RenderTarget;
 
 #[derive(Component)]
 pub struct MainCamera;
 
+#[allow(clippy::too_many_arguments)]
 pub fn ui_side_panel(
-    commands: Commands,
+    mut commands: Commands,
     mut egui_ctx: ResMut<EguiContext>,
     mut params: ResMut<Params>,
-    grid: Res<Grid>,
+    mut grid: ResMut<Grid>,
+    mut faces: ResMut<Faces>,
+    mask: Res<ClothMask>,
+    mesh: Option<Res<ObjMesh>>,
+    mut exporter: ResMut<Exporter>,
+    recorder: Res<Recorder>,
     edges: Query<Entity, With<Edge>>,
-    nodes: Query<(&Index, &mut Transform, &mut PreviousPosition)>,
+    nodes: ResetNodes,
+    mut wind_sources: Query<(Entity, &mut WindSource)>,
+    energy: Res<EnergyHistory>,
+    mut stability_warning: ResMut<StabilityWarning>,
+    substeps: Res<Substeps>,
+    mut bindings: ResMut<Bindings>,
 ) {
     egui::SidePanel::right("side_panel")
         .default_width(params.side_panel_width)
         .show(egui_ctx.ctx_mut(), |ui| {
+            let bound = |action: Action| {
+                bindings
+                    .get(action)
+                    .map_or("an unbound control".to_string(), |binding| binding.label())
+            };
+
             ui.heading("Instructions");
-            ui.label("Hold left mouse button to exert FORCE around the area.");
-            ui.label("Hold right mouse button to REMOVE links around the area.");
-            ui.label("Hold mouse wheel to MOVE the camera.");
-            ui.label("Scroll mouse wheel to ZOOM in/out the camera.");
+            ui.label(format!(
+                "Hold {} to exert FORCE around the area.",
+                bound(Action::Force)
+            ));
+            ui.label(format!(
+                "Hold {} to REMOVE links around the area.",
+                bound(Action::Cut)
+            ));
+            ui.label(format!("Hold {} to MOVE the camera.", bound(Action::Pan)));
+            ui.label(format!(
+                "Scroll mouse wheel or hold {} and {} to ZOOM in/out the camera.",
+                bound(Action::ZoomIn),
+                bound(Action::ZoomOut)
+            ));
+            ui.label(format!(
+                "Press {} to RESET, {} to PAUSE and {} to toggle WIND.",
+                bound(Action::Reset),
+                bound(Action::Pause),
+                bound(Action::ToggleWind)
+            ));
+            ui.label("On touch screens drag one finger for FORCE and rest it to REMOVE links.");
+            ui.label("Drag and pinch two fingers to MOVE and ZOOM the camera.");
+            ui.collapsing("Key bindings", |ui| bindings_ui(ui, &mut bindings));
 
             ui.separator();
             ui.heading("Simulation controls");
 
-            if ui.button("Reset").clicked() {
-                super::reset_nodes_position(commands, &params, grid, edges, nodes);
+            if let Some(warning) = stability_warning.0.clone() {
+                ui.horizontal(|ui| {
+                    ui.colored_label(egui::Color32::RED, warning);
+                    if ui.button("Dismiss").clicked() {
+                        stability_warning.0 = None;
+                    }
+                });
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Reset").clicked() {
+                    super::reset_nodes_position(
+                        &mut commands,
+                        &params,
+                        &mask,
+                        mesh.as_deref(),
+                        &mut grid,
+                        &mut faces,
+                        edges,
+                        nodes,
+                    );
+                }
+
+                let label = if params.paused { "Resume" } else { "Pause" };
+                if ui.button(label).clicked() {
+                    params.paused = !params.paused;
+                }
+            });
+
+            share_link_ui(ui, &params);
+
+            ui.horizontal(|ui| {
+                ui.label("Storage");
+                ui.radio_value(&mut params.storage, StorageBackend::Ecs, "ECS");
+                ui.radio_value(
+                    &mut params.storage,
+                    StorageBackend::Contiguous,
+                    "Contiguous",
+                );
+            });
+
+            if params.storage == StorageBackend::Ecs {
+                ui.horizontal(|ui| {
+                    ui.label("Springs");
+                    ui.radio_value(
+                        &mut params.spring_solver,
+                        SpringSolver::Parallel,
+                        "Parallel",
+                    );
+                    ui.radio_value(&mut params.spring_solver, SpringSolver::Serial, "Serial");
+                });
             }
 
             ui.add(egui::Slider::new(&mut params.g, 0.0..=5000.0).text("gravity"));
 
+            // Angle of the gravity direction, 0° pulls straight down
+            let direction = params.gravity_direction;
+            let mut angle = direction.x.atan2(-direction.y).to_degrees();
+            if ui
+                .add(
+                    egui::Slider::new(&mut angle, -180.0..=180.0)
+                        .text("gravity direction")
+                        .suffix("°"),
+                )
+                .changed()
+            {
+                let angle = angle.to_radians();
+                params.gravity_direction = Vec3::new(angle.sin(), -angle.cos(), 0.0);
+            }
+
+            ui.separator();
+            ui.heading("World bounds");
+
+            // The walls keep apart by at least one unit, the config validation rejects
+            // crossed walls as well
+            let unbounded = f32::NEG_INFINITY..=f32::INFINITY;
+            let left_limit = params
+                .bounds
+                .right_wall
+                .map_or(f32::INFINITY, |wall| wall - 1.0);
+            let right_limit = params
+                .bounds
+                .left_wall
+                .map_or(f32::NEG_INFINITY, |wall| wall + 1.0);
+
+            optional_value_ui(ui, "Floor", &mut params.bounds.floor, -1000.0, unbounded);
+            optional_value_ui(
+                ui,
+                "Left wall",
+                &mut params.bounds.left_wall,
+                -500.0,
+                f32::NEG_INFINITY..=left_limit,
+            );
+            optional_value_ui(
+                ui,
+                "Right wall",
+                &mut params.bounds.right_wall,
+                1500.0,
+                right_limit..=f32::INFINITY,
+            );
+            ui.add(
+                egui::Slider::new(&mut params.bounds.restitution, 0.0..=1.0).text("Restitution"),
+            );
+            ui.add(egui::Slider::new(&mut params.bounds.friction, 0.0..=1.0).text("Friction"));
+
+            ui.separator();
+            ui.heading("Stability");
+
+            ui.checkbox(&mut params.stability.enabled, "Detect explosions");
+            ui.add(
+                egui::Slider::new(&mut params.stability.max_velocity, 1000.0..=100000.0)
+                    .logarithmic(true)
+                    .text("Max velocity"),
+            );
+            ui.horizontal(|ui| {
+                let response = &mut params.stability.response;
+                ui.radio_value(response, ExplosionResponse::Pause, "Pause");
+                ui.radio_value(response, ExplosionResponse::ClampVelocities, "Clamp");
+                ui.radio_value(response, ExplosionResponse::Rollback, "Roll back");
+            });
+            ui.label(format!("Substep: {:.5} s", substeps.dt(params.dt)));
+
+            ui.separator();
+            ui.heading("Substeps");
+
+            let adaptive_steps = &mut params.adaptive_steps;
+            ui.checkbox(&mut adaptive_steps.enabled, "Adaptive substeps");
+            ui.add(egui::Slider::new(&mut adaptive_steps.min_steps, 1..=50).text("Min substeps"));
+            ui.add(egui::Slider::new(&mut adaptive_steps.max_steps, 1..=50).text("Max substeps"));
+            ui.add(
+                egui::Slider::new(&mut adaptive_steps.max_strain, 0.01..=1.0).text("Max strain"),
+            );
+            ui.add(
+                egui::Slider::new(&mut adaptive_steps.max_velocity, 100.0..=50000.0)
+                    .logarithmic(true)
+                    .text("Max velocity"),
+            );
+            ui.label(format!("Substeps: {}", substeps.count));
+
+            ui.separator();
+            ui.heading("Detached fragments");
+
+            ui.checkbox(&mut params.fragments.color, "Color fragments");
+            ui.horizontal(|ui| {
+                let action = &mut params.fragments.detached_action;
+                ui.radio_value(action, DetachedAction::Simulate, "Simulate");
+                ui.radio_value(action, DetachedAction::Sleep, "Sleep");
+                ui.radio_value(action, DetachedAction::Despawn, "Despawn");
+            });
+
             ui.separator();
             ui.heading("Rest lengths");
 
@@ -57,10 +252,99 @@ pub fn ui_side_panel(
             ui.heading("Spring coefficients");
 
             ui.add(egui::Slider::new(&mut params.k[0], 1.0..=5000.0).text("Structural k"));
+            ui.add(
+                egui::Slider::new(&mut params.bending_stiffness, 0.0..=1.0)
+                    .text("Bending stiffness"),
+            );
+            ui.checkbox(&mut params.enable_tethers, "Long-range attachments");
+
+            ui.separator();
+            ui.heading("Damping");
+
+            ui.horizontal(|ui| {
+                let model = &mut params.damping.model;
+                ui.radio_value(model, DampingModel::Multiplicative, "Factor");
+                ui.radio_value(model, DampingModel::AirDrag, "Air drag");
+                ui.radio_value(model, DampingModel::SpringAxis, "Spring axis");
+                ui.radio_value(model, DampingModel::Rayleigh, "Rayleigh");
+            });
+            match params.damping.model {
+                DampingModel::Multiplicative => {
+                    ui.add(
+                        egui::Slider::new(&mut params.dampen_factor, 0.9..=1.0)
+                            .text("Dampen factor"),
+                    );
+                }
+                DampingModel::AirDrag => {
+                    ui.add(
+                        egui::Slider::new(&mut params.damping.air_drag, 0.0..=20.0)
+                            .text("Air drag (1/s)"),
+                    );
+                }
+                DampingModel::SpringAxis => {
+                    ui.add(
+                        egui::Slider::new(&mut params.damping.spring_damping, 0.0..=200.0)
+                            .text("Spring damping (1/s)"),
+                    );
+                }
+                DampingModel::Rayleigh => {
+                    ui.add(
+                        egui::Slider::new(&mut params.damping.rayleigh_alpha, 0.0..=20.0)
+                            .text("Mass coefficient"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut params.damping.rayleigh_beta, 0.0..=0.05)
+                            .text("Stiffness coefficient"),
+                    );
+                }
+            }
 
             ui.separator();
             ui.heading("Wind");
             ui.checkbox(&mut params.enable_wind, "Enable wind");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut params.wind_model, WindModel::Push, "Push");
+                ui.radio_value(
+                    &mut params.wind_model,
+                    WindModel::Aerodynamic,
+                    "Aerodynamic",
+                );
+            });
+
+            if params.wind_model == WindModel::Aerodynamic {
+                ui.add(
+                    egui::Slider::new(&mut params.drag_coefficient, 0.0..=5.0)
+                        .text("Drag coefficient"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut params.lift_coefficient, 0.0..=5.0)
+                        .text("Lift coefficient"),
+                );
+                ui.add(
+                    egui::Slider::new(&mut params.through_wind, -3000.0..=3000.0)
+                        .text("Wind through the cloth"),
+                );
+            }
+
+            let mut explicit_domain = params.wind_domain.is_some();
+            if ui
+                .checkbox(&mut explicit_domain, "Explicit wind domain")
+                .changed()
+            {
+                params.wind_domain = explicit_domain.then_some(params.wind_domain());
+            }
+            if let Some(domain) = params.wind_domain.as_mut() {
+                vec2_ui(ui, "Domain min", &mut domain.min);
+                vec2_ui(ui, "Domain max", &mut domain.max);
+            }
+
+            wind_sources_ui(ui, &mut commands, &mut wind_sources);
+
+            ui.separator();
+            ui.collapsing("Energy", |ui| energy_ui(ui, &energy));
+
+            export_ui(ui, &mut params.export, &mut exporter);
+            record_ui(ui, &mut params.record, &recorder);
 
             ui.separator();
             ui.heading("Mouse force");
@@ -83,82 +367,348 @@ pub fn ui_side_panel(
         });
 }
 
+/// Scrolling graph of the cloth energy, with an export of the history as CSV
+fn energy_ui(ui: &mut egui::Ui, energy: &EnergyHistory) {
+    if let Some(last) = energy.samples.back() {
+        ui.label(format!("Total energy: {:.0}", last.total()));
+        ui.label(format!(
+            "Momentum: {:.0}, {:.0}, {:.0}",
+            last.momentum.x, last.momentum.y, last.momentum.z
+        ));
+    }
+
+    let line = |name: &str, value: fn(&EnergySample) -> f32| {
+        Line::new(Values::from_values_iter(
+            energy
+                .samples
+                .iter()
+                .map(|sample| Value::new(sample.time, value(sample))),
+        ))
+        .name(name)
+    };
+
+    Plot::new("energy_plot")
+        .height(150.0)
+        .legend(Legend::default())
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .show(ui, |plot_ui| {
+            plot_ui.line(line("Kinetic", |sample| sample.kinetic));
+            plot_ui.line(line("Gravitational", |sample| sample.gravitational));
+            plot_ui.line(line("Spring", |sample| sample.spring));
+            plot_ui.line(line("Total", |sample| sample.total()));
+        });
+
+    ui.horizontal(|ui| {
+        if ui.button("Copy CSV").clicked() {
+            ui.output().copied_text = energy.to_csv();
+        }
+
+        save_csv_button(ui, energy);
+    });
+}
+
+/// Writes the energy history to the working directory
+#[cfg(not(target_arch = "wasm32"))]
+fn save_csv_button(ui: &mut egui::Ui, energy: &EnergyHistory) {
+    if ui.button("Save CSV").clicked() {
+        match std::fs::write("energy.csv", energy.to_csv()) {
+            Ok(_) => println!("energy history written to energy.csv"),
+            Err(err) => eprintln!("could not write energy.csv: {}", err),
+        }
+    }
+}
+
+/// There is no file system on web, copying the CSV is the only export
+#[cfg(target_arch = "wasm32")]
+fn save_csv_button(_ui: &mut egui::Ui, _energy: &EnergyHistory) {}
+
+/// Binding of every action, stored as soon as one changes
+fn bindings_ui(ui: &mut egui::Ui, bindings: &mut Bindings) {
+    let mut changed = false;
+
+    for action in Action::ALL {
+        let mut binding = bindings.get(action);
+        ui.horizontal(|ui| {
+            ui.label(action.label());
+            egui::ComboBox::from_id_source(action.name())
+                .selected_text(binding.map_or("None".to_string(), |binding| binding.label()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut binding, None, "None");
+                    for choice in Binding::choices() {
+                        ui.selectable_value(&mut binding, Some(choice), choice.label());
+                    }
+                });
+        });
+
+        if binding != bindings.get(action) {
+            bindings.set(action, binding);
+            changed = true;
+        }
+
+        let conflicts = bindings.conflicts(action);
+        if !conflicts.is_empty() {
+            let labels: Vec<&str> = conflicts.iter().map(|other| other.label()).collect();
+            ui.colored_label(
+                egui::Color32::YELLOW,
+                format!("Also bound to {}", labels.join(", ")),
+            );
+        }
+    }
+
+    if ui.button("Restore defaults").clicked() {
+        *bindings = Bindings::default();
+        changed = true;
+    }
+
+    if changed {
+        bindings.save();
+    }
+}
+
+/// Link to the page with the current parameters in the query string
+#[cfg(target_arch = "wasm32")]
+fn share_link_ui(ui: &mut egui::Ui, params: &Params) {
+    let location = match web_sys::window() {
+        Some(window) => window.location(),
+        None => return,
+    };
+    let page = format!(
+        "{}{}",
+        location.origin().unwrap_or_default(),
+        location.pathname().unwrap_or_default()
+    );
+    let query = crate::config::query_string(params);
+    let link = if query.is_empty() {
+        page
+    } else {
+        format!("{}?{}", page, query)
+    };
+
+    if ui.button("Copy shareable link").clicked() {
+        ui.output().copied_text = link.clone();
+    }
+    // Selectable as well, for browsers which don't give the page clipboard access
+    ui.add(egui::TextEdit::singleline(&mut link.as_str()));
+}
+
+/// Parameters are passed on the command line on desktop
+#[cfg(not(target_arch = "wasm32"))]
+fn share_link_ui(_ui: &mut egui::Ui, _params: &Params) {}
+
+/// Export of the cloth surface to OBJ or glTF files and of the edges to SVG
+#[cfg(not(target_arch = "wasm32"))]
+fn export_ui(ui: &mut egui::Ui, options: &mut ExportOptions, exporter: &mut Exporter) {
+    use super::export::ExportFormat;
+
+    ui.separator();
+    ui.heading("Export");
+
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut options.format, ExportFormat::Obj, "OBJ");
+        ui.radio_value(&mut options.format, ExportFormat::Gltf, "glTF");
+    });
+    ui.add(egui::Slider::new(&mut options.interval, 0..=100).text("Every n-th update (0 = off)"));
+    if ui.button("Export frame").clicked() {
+        exporter.requested = true;
+    }
+    ui.label(format!("Frames written: {}", exporter.frames));
+
+    ui.checkbox(&mut options.svg_strain_colors, "Color SVG edges by strain");
+    ui.checkbox(&mut options.svg_nodes, "Draw SVG nodes");
+    if ui.button("Export SVG").clicked() {
+        exporter.svg_requested = true;
+    }
+}
+
+/// There is no file system on web
+#[cfg(target_arch = "wasm32")]
+fn export_ui(_ui: &mut egui::Ui, _options: &mut ExportOptions, _exporter: &mut Exporter) {}
+
+/// Recording of PNG frames, drawn without the window so the frames are repeatable
+#[cfg(not(target_arch = "wasm32"))]
+fn record_ui(ui: &mut egui::Ui, options: &mut RecordOptions, recorder: &Recorder) {
+    ui.separator();
+    ui.heading("Record");
+
+    ui.checkbox(&mut options.enabled, "Record PNG frames");
+    ui.horizontal(|ui| {
+        ui.label("Size");
+        ui.add(egui::DragValue::new(&mut options.width).clamp_range(16..=7680));
+        ui.add(egui::DragValue::new(&mut options.height).clamp_range(16..=4320));
+    });
+    ui.add(egui::Slider::new(&mut options.node_radius, 0.0..=10.0).text("Node radius"));
+    match recorder.directory.as_ref() {
+        Some(directory) => ui.label(format!(
+            "Frames recorded: {} in {}",
+            recorder.frames, directory
+        )),
+        None => ui.label(format!("Frames recorded: {}", recorder.frames)),
+    };
+}
+
+/// There is no file system on web
+#[cfg(target_arch = "wasm32")]
+fn record_ui(_ui: &mut egui::Ui, _options: &mut RecordOptions, _recorder: &Recorder) {}
+
+/// Lists all wind sources with their editable properties
+fn wind_sources_ui(
+    ui: &mut egui::Ui,
+    commands: &mut Commands,
+    wind_sources: &mut Query<(Entity, &mut WindSource)>,
+) {
+    for (i, (entity, mut source)) in wind_sources.iter_mut().enumerate() {
+        ui.collapsing(format!("{} wind #{}", source.name(), i + 1), |ui| {
+            match &mut *source {
+                WindSource::Directional { rect, force } => {
+                    vec2_ui(ui, "Min", &mut rect.min);
+                    vec2_ui(ui, "Max", &mut rect.max);
+                    xy_ui(ui, "Force", &mut force.x, &mut force.y);
+                }
+                WindSource::Fan {
+                    origin,
+                    direction,
+                    force,
+                    radius,
+                } => {
+                    vec2_ui(ui, "Origin", origin);
+                    vec2_ui(ui, "Direction", direction);
+                    ui.add(egui::Slider::new(force, 0.0..=10000.0).text("Force"));
+                    ui.add(egui::Slider::new(radius, 10.0..=3000.0).text("Radius"));
+                }
+                WindSource::Vortex {
+                    center,
+                    force,
+                    radius,
+                } => {
+                    vec2_ui(ui, "Center", center);
+                    ui.add(egui::Slider::new(force, -10000.0..=10000.0).text("Force"));
+                    ui.add(egui::Slider::new(radius, 10.0..=3000.0).text("Radius"));
+                }
+            }
+
+            if ui.button("Remove").clicked() {
+                commands.entity(entity).despawn();
+            }
+        });
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add directional").clicked() {
+            commands.spawn().insert(WindSource::directional());
+        }
+        if ui.button("Add fan").clicked() {
+            commands.spawn().insert(WindSource::fan());
+        }
+        if ui.button("Add vortex").clicked() {
+            commands.spawn().insert(WindSource::vortex());
+        }
+    });
+}
+
+/// Checkbox enabling the value, followed by its editor when enabled. The value is kept
+/// within the range.
+fn optional_value_ui(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut Option<f32>,
+    default: f32,
+    range: RangeInclusive<f32>,
+) {
+    ui.horizontal(|ui| {
+        let mut enabled = value.is_some();
+        if ui.checkbox(&mut enabled, label).changed() {
+            *value = enabled.then_some(default);
+        }
+
+        if let Some(value) = value.as_mut() {
+            *value = value.clamp(*range.start(), *range.end());
+            ui.add(egui::DragValue::new(value).clamp_range(range));
+        }
+    });
+}
+
+fn vec2_ui(ui: &mut egui::Ui, label: &str, value: &mut Vec2) {
+    xy_ui(ui, label, &mut value.x, &mut value.y);
+}
+
+fn xy_ui(ui: &mut egui::Ui, label: &str, x: &mut f32, y: &mut f32) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.add(egui::DragValue::new(x).prefix("x: "));
+        ui.add(egui::DragValue::new(y).prefix("y: "));
+    });
+}
+
+/// Nodes as seen by the mouse and touch interaction
+type InteractionNodes<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Transform,
+        &'static mut Force,
+        Option<&'static Pinned>,
+    ),
+    (With<Index>, Without<MainCamera>),
+>;
+
+/// Force, cut, pan and zoom, ignored while the side panel has the keyboard or pointer
+#[allow(clippy::too_many_arguments)]
 pub fn handle_mouse_interaction(
     mut commands: Commands,
+    mut egui_ctx: ResMut<EguiContext>,
     params: Res<Params>,
     buttons: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    bindings: Res<Bindings>,
+    touches: Res<Touches>,
+    time: Res<Time>,
     wnds: Res<Windows>,
+    mut gestures: Local<TouchGestures>,
     mut ev_motion: EventReader<MouseMotion>,
     mut ev_scroll: EventReader<MouseWheel>,
     mut edges: Query<(Entity, &Edge)>,
     mut q_camera: Query<(&Camera, &GlobalTransform, &mut Transform), With<MainCamera>>,
-    mut nodes: Query<(&Transform, &mut Force, Option<&Pinned>), (With<Index>, Without<MainCamera>)>,
+    mut nodes: InteractionNodes,
 ) {
     // assuming there is exactly one main camera entity, so query::single() is OK
     let (camera, camera_global_transform, mut camera_transform) = q_camera.single_mut();
 
-    if buttons.pressed(MouseButton::Left) || buttons.pressed(MouseButton::Right) {
-        // get the window that the camera is displaying to (or the primary window)
-        let wnd = if let RenderTarget::Window(id) = camera.target {
-            wnds.get(id).unwrap()
-        } else {
-            wnds.get_primary().unwrap()
-        };
+    // get the window that the camera is displaying to (or the primary window)
+    let wnd = if let RenderTarget::Window(id) = camera.target {
+        wnds.get(id).unwrap()
+    } else {
+        wnds.get_primary().unwrap()
+    };
+    let to_world =
+        |screen_pos: Vec2| screen_to_world(camera, camera_global_transform, wnd, screen_pos);
 
+    let input = binding_input(&mut egui_ctx, &keys, &buttons);
+    let force = bindings.pressed(Action::Force, &input);
+    let cut = bindings.pressed(Action::Cut, &input);
+    if force || cut {
         // check if the cursor is inside the window and get its position
         if let Some(screen_pos) = wnd.cursor_position() {
-            // get the size of the window
-            let window_size = Vec2::new(wnd.width() as f32, wnd.height() as f32);
-
-            // convert screen position [0..resolution] to ndc [-1..1] (gpu coordinates)
-            let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
-
-            // matrix for undoing the projection and camera camera_transform
-            let ndc_to_world =
-                camera_global_transform.compute_matrix() * camera.projection_matrix().inverse();
-
-            // use it to convert ndc to world-space coordinates
-            let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
-
-            // reduce it to a 2D value
-            let world_pos: Vec2 = world_pos.truncate();
+            let world_pos = to_world(screen_pos);
 
-            eprintln!("World coords: {}/{}", world_pos.x, world_pos.y);
-
-            if buttons.pressed(MouseButton::Left) {
-                for (pos, mut force, pinned) in nodes.iter_mut() {
-                    if pos.translation.truncate().distance(world_pos) < 150.0 {
-                        if let None = pinned {
-                            force.0 += params.mouse_force;
-                        }
-                    }
-                }
+            if force {
+                apply_force_at(world_pos, &params, &mut nodes);
             }
-            if buttons.pressed(MouseButton::Right) {
-                for (entity, edge) in edges.iter_mut() {
-                    let [(a_pos, _, _), (b_pos, _, _)] = nodes.many_mut([edge.a, edge.b]);
-
-                    if a_pos.translation.truncate().distance(world_pos) <= params.r[0]
-                        || b_pos.translation.truncate().distance(world_pos) <= params.r[0]
-                    {
-                        // Remove the first matching edge - to avoid having big holes
-                        commands.entity(entity).despawn();
-                        break;
-                    }
-                }
+            if cut {
+                cut_at(world_pos, &params, &mut commands, &mut edges, &mut nodes);
             }
         }
     }
 
-    // Handle panning with middle mouse button
-    if buttons.pressed(MouseButton::Middle) {
+    // Handle panning while the pan binding is held
+    if bindings.pressed(Action::Pan, &input) {
         let mut pan = Vec2::ZERO;
         for ev in ev_motion.iter() {
             pan += ev.delta;
         }
 
-        camera_transform.translation.x -= 1.5 * pan.x;
-        camera_transform.translation.y += 1.5 * pan.y;
+        pan_camera(&mut camera_transform, pan);
     }
 
     // Handle zooming in
@@ -167,17 +717,197 @@ pub fn handle_mouse_interaction(
         scroll += ev.y;
     }
 
+    // Held zoom bindings act like a scroll step on every update
+    if bindings.pressed(Action::ZoomIn, &input) {
+        scroll += 1.0;
+    }
+    if bindings.pressed(Action::ZoomOut, &input) {
+        scroll -= 1.0;
+    }
+
     if scroll.abs() > 0.0 {
-        camera_transform.scale -= scroll / 50.0;
-        camera_transform.scale = camera_transform
-            .scale
-            .clamp(Vec3::splat(0.1), Vec3::splat(3.0));
+        let scale = camera_transform.scale - Vec3::splat(scroll / 50.0);
+        zoom_camera(&mut camera_transform, scale);
+    }
+
+    let now = time.seconds_since_startup();
+    match gestures.update(now, &fingers(wnd, &touches)) {
+        Some(Gesture::Force(screen_pos)) => {
+            apply_force_at(to_world(screen_pos), &params, &mut nodes)
+        }
+        Some(Gesture::Cut(screen_pos)) => cut_at(
+            to_world(screen_pos),
+            &params,
+            &mut commands,
+            &mut edges,
+            &mut nodes,
+        ),
+        Some(Gesture::PanZoom { pan, zoom }) => {
+            pan_camera(&mut camera_transform, pan);
+            if let Some(zoom) = zoom {
+                let scale = camera_transform.scale * zoom;
+                zoom_camera(&mut camera_transform, scale);
+            }
+        }
+        None => {}
+    }
+}
+
+/// Reset, pause and wind toggle, ignored while the side panel has the keyboard or pointer
+#[allow(clippy::too_many_arguments)]
+pub fn handle_shortcuts(
+    mut commands: Commands,
+    mut egui_ctx: ResMut<EguiContext>,
+    mut params: ResMut<Params>,
+    mut grid: ResMut<Grid>,
+    mut faces: ResMut<Faces>,
+    mask: Res<ClothMask>,
+    mesh: Option<Res<ObjMesh>>,
+    bindings: Res<Bindings>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    edges: Query<Entity, With<Edge>>,
+    nodes: ResetNodes,
+) {
+    let input = binding_input(&mut egui_ctx, &keys, &buttons);
+    let triggered = |action: Action| bindings.just_pressed(action, &input);
+
+    if triggered(Action::Pause) {
+        params.paused = !params.paused;
+    }
+    if triggered(Action::ToggleWind) {
+        params.enable_wind = !params.enable_wind;
+    }
+    if triggered(Action::Reset) {
+        super::reset_nodes_position(
+            &mut commands,
+            &params,
+            &mask,
+            mesh.as_deref(),
+            &mut grid,
+            &mut faces,
+            edges,
+            nodes,
+        );
+    }
+}
+
+/// Input for the bindings, with the keyboard and pointer taken while egui uses them
+fn binding_input<'a>(
+    egui_ctx: &mut EguiContext,
+    keys: &'a Input<KeyCode>,
+    buttons: &'a Input<MouseButton>,
+) -> BindingInput<'a> {
+    let ctx = egui_ctx.ctx_mut();
+    BindingInput {
+        keys,
+        buttons,
+        keyboard_free: !ctx.wants_keyboard_input(),
+        pointer_free: !ctx.is_pointer_over_area(),
+    }
+}
+
+/// Fingers on the screen as reported by Bevy. Bevy flips the touches of mobile windows, like
+/// the cursor they have y up there, everywhere else they start at the top.
+#[cfg(not(target_arch = "wasm32"))]
+fn fingers(wnd: &Window, touches: &Touches) -> Vec<Finger> {
+    touches
+        .iter()
+        .map(|touch| {
+            let pos = touch.position();
+            let position = if cfg!(any(target_os = "android", target_os = "ios")) {
+                pos
+            } else {
+                Vec2::new(pos.x, wnd.height() - pos.y)
+            };
+            Finger {
+                id: touch.id(),
+                position,
+            }
+        })
+        .collect()
+}
+
+/// Fingers on the canvas, winit reports no touches on web
+#[cfg(target_arch = "wasm32")]
+fn fingers(wnd: &Window, _touches: &Touches) -> Vec<Finger> {
+    super::gestures::web::fingers(wnd)
+}
+
+/// Converts a window position to world coordinates
+fn screen_to_world(
+    camera: &Camera,
+    camera_global_transform: &GlobalTransform,
+    wnd: &Window,
+    screen_pos: Vec2,
+) -> Vec2 {
+    // get the size of the window
+    let window_size = Vec2::new(wnd.width() as f32, wnd.height() as f32);
+
+    // convert screen position [0..resolution] to ndc [-1..1] (gpu coordinates)
+    let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
+
+    // matrix for undoing the projection and camera camera_transform
+    let ndc_to_world =
+        camera_global_transform.compute_matrix() * camera.projection_matrix().inverse();
+
+    // use it to convert ndc to world-space coordinates
+    let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
+
+    // reduce it to a 2D value
+    world_pos.truncate()
+}
+
+/// Pushes the free nodes around the position. Nothing consumes forces while paused, so
+/// they are not added then, or they would pile up into one huge push on resume.
+fn apply_force_at(world_pos: Vec2, params: &Params, nodes: &mut InteractionNodes) {
+    if params.paused {
+        return;
     }
+
+    for (pos, mut force, pinned) in nodes.iter_mut() {
+        if pos.translation.truncate().distance(world_pos) < 150.0 {
+            if let None = pinned {
+                force.0 += params.mouse_force;
+            }
+        }
+    }
+}
+
+/// Removes an edge with a node at the position
+fn cut_at(
+    world_pos: Vec2,
+    params: &Params,
+    commands: &mut Commands,
+    edges: &mut Query<(Entity, &Edge)>,
+    nodes: &mut InteractionNodes,
+) {
+    for (entity, edge) in edges.iter_mut() {
+        let [(a_pos, _, _), (b_pos, _, _)] = nodes.many_mut([edge.a, edge.b]);
+
+        if a_pos.translation.truncate().distance(world_pos) <= params.r[0]
+            || b_pos.translation.truncate().distance(world_pos) <= params.r[0]
+        {
+            // Remove the first matching edge - to avoid having big holes
+            commands.entity(entity).despawn();
+            break;
+        }
+    }
+}
+
+/// Moves the camera by a screen space motion with y pointing down
+fn pan_camera(camera_transform: &mut Transform, pan: Vec2) {
+    camera_transform.translation.x -= 1.5 * pan.x;
+    camera_transform.translation.y += 1.5 * pan.y;
+}
+
+fn zoom_camera(camera_transform: &mut Transform, scale: Vec3) {
+    camera_transform.scale = scale.clamp(Vec3::splat(0.1), Vec3::splat(3.0));
 }
 
-/// Triggers system if the "Enable wind" checkbox is selected
+/// Triggers system if the "Enable wind" checkbox is selected and the simulation is running
 pub fn run_if_wind_enabled(params: Res<Params>) -> ShouldRun {
-    if params.enable_wind {
+    if params.enable_wind && !params.paused {
         ShouldRun::Yes
     } else {
         ShouldRun::No
@@ -0,0 +1,216 @@
+use super::aerodynamics::apply_aerodynamic_forces;
+use super::mesh::{Faces, Links};
+use super::physics::{Awake, Edge, Force, Pinned, PreviousPosition};
+use super::substeps::Substeps;
+use super::Params;
+use bevy::prelude::*;
+use bevy::sprite::Rect;
+
+/// Awake nodes as seen by the wind, which only adds forces
+pub type WindNodes<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static Transform,
+        &'static PreviousPosition,
+        &'static mut Force,
+        Option<&'static Pinned>,
+    ),
+    Awake,
+>;
+
+/// How the wind acts on the cloth
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum WindModel {
+    /// Wind pushes every node inside the wave directly
+    #[default]
+    Push,
+    /// Wind exerts drag and lift on cloth faces depending on their orientation
+    Aerodynamic,
+}
+
+/// A single source of wind, the total wind is the sum of all sources. The force pushes nodes
+/// directly, the aerodynamic model turns it into an air velocity first.
+#[derive(Component, Clone, Copy)]
+pub enum WindSource {
+    /// Wave travelling along the x axis, blowing with `force` inside its rectangle
+    Directional { rect: Rect, force: Vec3 },
+    /// Fan at `origin` blowing towards `direction`, falls off linearly until `radius`
+    Fan {
+        origin: Vec2,
+        direction: Vec2,
+        force: f32,
+        radius: f32,
+    },
+    /// Wind swirling counter-clockwise around `center`, falls off linearly until `radius`
+    Vortex {
+        center: Vec2,
+        force: f32,
+        radius: f32,
+    },
+}
+
+impl WindSource {
+    pub fn directional() -> Self {
+        WindSource::Directional {
+            rect: Rect {
+                min: Vec2::new(0.0, -1000.0),
+                max: Vec2::new(500.0, 0.0),
+            },
+            force: Vec3::new(1000.0, 300.0, 0.0),
+        }
+    }
+
+    pub fn fan() -> Self {
+        WindSource::Fan {
+            origin: Vec2::new(-200.0, -300.0),
+            direction: Vec2::X,
+            force: 3000.0,
+            radius: 1200.0,
+        }
+    }
+
+    pub fn vortex() -> Self {
+        WindSource::Vortex {
+            center: Vec2::new(500.0, -300.0),
+            force: 3000.0,
+            radius: 400.0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindSource::Directional { .. } => "Directional",
+            WindSource::Fan { .. } => "Fan",
+            WindSource::Vortex { .. } => "Vortex",
+        }
+    }
+
+    /// Wind force exerted by this source at the given point
+    pub fn force_at(&self, point: Vec3) -> Vec3 {
+        let point = point.truncate();
+
+        match *self {
+            WindSource::Directional { rect, force } => {
+                if point.x >= rect.min.x
+                    && point.x <= rect.max.x
+                    && point.y >= rect.min.y
+                    && point.y <= rect.max.y
+                {
+                    force
+                } else {
+                    Vec3::ZERO
+                }
+            }
+            WindSource::Fan {
+                origin,
+                direction,
+                force,
+                radius,
+            } => {
+                let direction = direction.normalize_or_zero();
+                let offset = point - origin;
+                // Only blow forwards, strongest along the fan axis
+                let alignment = offset.normalize_or_zero().dot(direction).max(0.0);
+
+                (direction * force * alignment * falloff(offset.length(), radius)).extend(0.0)
+            }
+            WindSource::Vortex {
+                center,
+                force,
+                radius,
+            } => {
+                let offset = point - center;
+                let tangent = offset.perp().normalize_or_zero();
+
+                (tangent * force * falloff(offset.length(), radius)).extend(0.0)
+            }
+        }
+    }
+
+    /// Moves the source with its own wind, wrapping around once it leaves `domain`
+    fn advance(&mut self, dt: f32, domain: Rect) {
+        if let WindSource::Directional { rect, force } = self {
+            let width = domain.max.x - domain.min.x;
+
+            rect.min.x += force.x * dt;
+            rect.max.x += force.x * dt;
+
+            if width <= 0.0 {
+                return;
+            }
+
+            if rect.min.x >= domain.max.x {
+                rect.min.x -= width;
+                rect.max.x -= width;
+            } else if rect.max.x <= domain.min.x {
+                rect.min.x += width;
+                rect.max.x += width;
+            }
+        }
+    }
+}
+
+/// The sources are tuned as forces on single nodes. The aerodynamic model needs the air
+/// velocity instead, which is the one whose drag on a face of rest size equals the force.
+fn air_velocity_of(force: Vec3, params: &Params) -> Vec3 {
+    let face_area = 0.5 * params.r[0] * params.r[0];
+    let drag_per_square_speed = 0.5 * params.air_density * params.drag_coefficient * face_area;
+    if drag_per_square_speed <= f32::EPSILON {
+        return Vec3::ZERO;
+    }
+
+    force.normalize_or_zero() * (force.length() / drag_per_square_speed).sqrt()
+}
+
+/// Linear falloff from 1 at the source to 0 at `radius`
+fn falloff(distance: f32, radius: f32) -> f32 {
+    if radius <= 0.0 {
+        return 0.0;
+    }
+
+    (1.0 - distance / radius).max(0.0)
+}
+
+pub fn apply_wind(
+    params: Res<Params>,
+    substeps: Res<Substeps>,
+    faces: Res<Faces>,
+    edges: Query<&Edge>,
+    mut sources: Query<&mut WindSource>,
+    mut nodes: WindNodes,
+) {
+    let dt = params.dt;
+
+    let domain = params.wind_domain();
+    for mut source in sources.iter_mut() {
+        source.advance(dt, domain);
+    }
+
+    // Wind at a given point is the sum of all sources
+    let wind_at = |point: Vec3| -> Vec3 {
+        sources
+            .iter()
+            .map(|source| source.force_at(point))
+            .fold(Vec3::ZERO, |acc, f| acc + f)
+    };
+
+    match params.wind_model {
+        WindModel::Push => {
+            for (pos, _, mut node_force, pinned) in nodes.iter_mut() {
+                if pinned.is_none() {
+                    node_force.0 += wind_at(pos.translation);
+                }
+            }
+        }
+        WindModel::Aerodynamic => {
+            let links = Links::from_edges(edges.iter());
+            let step_dt = substeps.dt(dt);
+            let through_wind = Vec3::Z * params.through_wind;
+            let air_velocity =
+                |point: Vec3| air_velocity_of(wind_at(point), &params) + through_wind;
+
+            apply_aerodynamic_forces(step_dt, &params, &faces, &links, air_velocity, &mut nodes);
+        }
+    }
+}
@@ -0,0 +1,988 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use cloth_simulation_bevy_rust::simulation::{
+    AdaptiveSteps, DampingModel, DampingOptions, DetachedAction, Edge, ExplosionResponse,
+    FragmentOptions, Index, ObjMesh, Params, Pinned, PreviousPosition, Simulation, SpringSolver,
+    StabilityOptions, StorageBackend, Substeps, Tethers, WindModel, WindSource, WorldBounds,
+};
+
+/// Maximum distance between a node and its golden position
+const TOLERANCE: f32 = 1e-3;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, StageLabel)]
+struct PhysicsStage;
+
+fn params() -> Params {
+    Params {
+        num_nodes_x: 20,
+        num_nodes_y: 12,
+        dt: 0.025,
+        m: 1.0,
+        g: 1000.0,
+        gravity_direction: Vec3::new(0.0, -1.0, 0.0),
+        r: Vec3::new(20.0, 0.0, 0.0),
+        k: Vec3::new(3600.0, 1.0, 1.0),
+        dampen_factor: 0.99,
+        air_density: 0.00001,
+        drag_coefficient: 1.0,
+        lift_coefficient: 0.5,
+        ..Default::default()
+    }
+}
+
+/// Headless app running one physics update per `App::update`
+fn app(params: Params) -> App {
+    app_with(Simulation::new(params))
+}
+
+fn app_with(simulation: Simulation) -> App {
+    let mut app = App::new();
+    simulation.setup_world(&mut app.world);
+    Simulation::add_physics_stage(&mut app, PhysicsStage, Simulation::physics_stage());
+    app
+}
+
+fn step(app: &mut App, ticks: usize) {
+    for _ in 0..ticks {
+        app.update();
+    }
+}
+
+/// Node positions ordered by their index in the grid
+fn positions(app: &mut App) -> Vec<(Index, Vec3)> {
+    let mut positions: Vec<(Index, Vec3)> = app
+        .world
+        .query::<(&Index, &Transform)>()
+        .iter(&app.world)
+        .map(|(index, transform)| (*index, transform.translation))
+        .collect();
+    positions.sort_by_key(|(index, _)| (index.y, index.x));
+    positions
+}
+
+/// Despawns every edge between the row and the one below it, returns the row of every node
+fn cut_below_row(app: &mut App, row: usize) -> HashMap<Entity, usize> {
+    let rows: HashMap<Entity, usize> = app
+        .world
+        .query::<(Entity, &Index)>()
+        .iter(&app.world)
+        .map(|(entity, index)| (entity, index.y))
+        .collect();
+    let cut: Vec<Entity> = app
+        .world
+        .query::<(Entity, &Edge)>()
+        .iter(&app.world)
+        .filter(|(_, edge)| {
+            rows[&edge.a].min(rows[&edge.b]) == row && rows[&edge.a] != rows[&edge.b]
+        })
+        .map(|(entity, _)| entity)
+        .collect();
+    assert!(!cut.is_empty());
+
+    for entity in cut {
+        app.world.despawn(entity);
+    }
+    rows
+}
+
+/// Compares positions against `tests/golden/<name>.txt`. Running with `UPDATE_GOLDEN=1`
+/// writes the current positions instead, a missing golden file fails the test.
+fn assert_golden(name: &str, positions: &[(Index, Vec3)]) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+        .iter()
+        .collect::<PathBuf>()
+        .with_extension("txt");
+
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        let mut contents = String::new();
+        for (index, pos) in positions {
+            writeln!(
+                contents,
+                "{} {} {} {} {}",
+                index.x, index.y, pos.x, pos.y, pos.z
+            )
+            .unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+        eprintln!("wrote golden file {}", path.display());
+        return;
+    }
+
+    let golden = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "could not read golden file {}: {}, run with UPDATE_GOLDEN=1 to write it",
+            path.display(),
+            err
+        )
+    });
+    let golden: Vec<(usize, usize, Vec3)> = golden
+        .lines()
+        .map(|line| {
+            let values: Vec<&str> = line.split_whitespace().collect();
+            let number = |i: usize| values[i].parse::<f32>().unwrap();
+            (
+                values[0].parse().unwrap(),
+                values[1].parse().unwrap(),
+                Vec3::new(number(2), number(3), number(4)),
+            )
+        })
+        .collect();
+
+    assert_eq!(golden.len(), positions.len(), "number of nodes differs");
+    for ((x, y, expected), (index, actual)) in golden.iter().zip(positions) {
+        assert_eq!((*x, *y), (index.x, index.y));
+        assert!(
+            expected.distance(*actual) <= TOLERANCE,
+            "node {}x{} is at {}, expected {}",
+            x,
+            y,
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn golden_gravity_and_springs() {
+    let mut app = app(params());
+    step(&mut app, 200);
+    assert_golden("gravity_and_springs", &positions(&mut app));
+}
+
+#[test]
+fn golden_push_wind() {
+    let mut app = app(Params {
+        enable_wind: true,
+        wind_model: WindModel::Push,
+        ..params()
+    });
+    step(&mut app, 200);
+    assert_golden("push_wind", &positions(&mut app));
+}
+
+#[test]
+fn golden_aerodynamic_wind() {
+    let mut app = app(Params {
+        enable_wind: true,
+        wind_model: WindModel::Aerodynamic,
+        through_wind: 500.0,
+        ..params()
+    });
+    step(&mut app, 200);
+    assert_golden("aerodynamic_wind", &positions(&mut app));
+}
+
+#[test]
+fn golden_contiguous_storage() {
+    let mut app = app(Params {
+        storage: StorageBackend::Contiguous,
+        ..params()
+    });
+    step(&mut app, 200);
+    assert_golden("contiguous_storage", &positions(&mut app));
+}
+
+#[test]
+fn parallel_springs_match_serial_ones() {
+    // Large enough for several batches of edges per color
+    let run = |spring_solver| {
+        let mut app = app(Params {
+            num_nodes_x: 40,
+            num_nodes_y: 40,
+            spring_solver,
+            ..params()
+        });
+        step(&mut app, 20);
+        positions(&mut app)
+            .into_iter()
+            .map(|(_, pos)| pos)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(run(SpringSolver::Parallel), run(SpringSolver::Serial));
+}
+
+#[test]
+fn pinned_nodes_never_move() {
+    for storage in [StorageBackend::Ecs, StorageBackend::Contiguous] {
+        let mut app = app(Params {
+            storage,
+            enable_wind: true,
+            ..params()
+        });
+
+        let pinned = |app: &mut App| -> Vec<Vec3> {
+            app.world
+                .query_filtered::<&Transform, With<Pinned>>()
+                .iter(&app.world)
+                .map(|transform| transform.translation)
+                .collect()
+        };
+
+        let initial = pinned(&mut app);
+        assert!(!initial.is_empty());
+
+        for _ in 0..20 {
+            step(&mut app, 10);
+            assert_eq!(pinned(&mut app), initial);
+        }
+    }
+}
+
+#[test]
+fn positions_stay_finite() {
+    let mut app = app(params());
+
+    for _ in 0..20 {
+        step(&mut app, 10);
+        for (index, pos) in positions(&mut app) {
+            assert!(
+                pos.is_finite(),
+                "node {}x{} is at {}",
+                index.x,
+                index.y,
+                pos
+            );
+        }
+    }
+}
+
+#[test]
+fn adaptive_substeps_are_deterministic() {
+    let params = Params {
+        adaptive_steps: AdaptiveSteps {
+            enabled: true,
+            min_steps: 2,
+            max_steps: 20,
+            max_strain: 0.05,
+            max_velocity: 500.0,
+        },
+        ..params()
+    };
+
+    let mut first = app(params);
+    let mut second = app(params);
+    step(&mut first, 200);
+    step(&mut second, 200);
+
+    assert!(positions(&mut first) == positions(&mut second));
+}
+
+#[test]
+fn adaptive_substeps_follow_the_motion() {
+    let mut app = app(Params {
+        g: 0.0,
+        adaptive_steps: AdaptiveSteps {
+            enabled: true,
+            min_steps: 2,
+            max_steps: 20,
+            max_strain: 0.05,
+            max_velocity: 500.0,
+        },
+        ..params()
+    });
+    let count = |app: &App| app.world.resource::<Substeps>().count;
+
+    // Without gravity the cloth rests, so the controller settles at the minimum
+    step(&mut app, 100);
+    assert_eq!(count(&app), 2);
+
+    // Kicking the free nodes sideways stretches the cloth and makes it fast
+    for (mut prev_pos, pinned) in app
+        .world
+        .query::<(&mut PreviousPosition, Option<&Pinned>)>()
+        .iter_mut(&mut app.world)
+    {
+        if pinned.is_none() {
+            prev_pos.0.x -= 20.0;
+        }
+    }
+    step(&mut app, 1);
+    assert!(count(&app) > 2, "substeps stayed at {}", count(&app));
+
+    // Damping settles the cloth again
+    step(&mut app, 2000);
+    assert_eq!(count(&app), 2);
+}
+
+#[test]
+fn floor_stops_falling_cloth() {
+    let floor = -500.0;
+    let mut app = app(Params {
+        bounds: WorldBounds {
+            floor: Some(floor),
+            restitution: 0.3,
+            friction: 0.5,
+            ..Default::default()
+        },
+        ..params()
+    });
+
+    // Release the cloth
+    let pinned: Vec<Entity> = app
+        .world
+        .query_filtered::<Entity, With<Pinned>>()
+        .iter(&app.world)
+        .collect();
+    for entity in pinned {
+        app.world.entity_mut(entity).remove::<Pinned>();
+    }
+
+    step(&mut app, 300);
+
+    for (index, pos) in positions(&mut app) {
+        assert!(
+            pos.y >= floor - TOLERANCE,
+            "node {}x{} fell through the floor to {}",
+            index.x,
+            index.y,
+            pos.y
+        );
+    }
+}
+
+#[test]
+fn obj_mesh_becomes_cloth() {
+    // Unit square split into two triangles, the top side is pinned
+    let mesh = ObjMesh::parse(
+        "v 0 0 0\n\
+         v 1 0 0\n\
+         v 1 1 0\n\
+         v 0 1 0\n\
+         f 1 2 3 4\n",
+    )
+    .unwrap();
+    let mut app = app_with(Simulation::new(params()).with_mesh(mesh));
+
+    let edges = app.world.query::<&Edge>().iter(&app.world).count();
+    let pinned = app
+        .world
+        .query_filtered::<(), With<Pinned>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(edges, 5);
+    assert_eq!(pinned, 2);
+
+    step(&mut app, 100);
+    for (_, pos) in positions(&mut app) {
+        assert!(pos.is_finite());
+    }
+}
+
+/// Two triangles hanging from a shared top side, the second one folded out of the plane
+fn folded_mesh() -> ObjMesh {
+    ObjMesh::parse(
+        "v 0 1 0\n\
+         v 1 1 0\n\
+         v 0.5 0 0\n\
+         v 0.5 0 1\n\
+         f 1 2 3\n\
+         f 2 1 4\n",
+    )
+    .unwrap()
+}
+
+#[test]
+fn bending_keeps_a_folded_mesh_folded() {
+    let mesh = folded_mesh();
+    let folded_tip = |bending_stiffness: f32| {
+        let params = Params {
+            bending_stiffness,
+            ..params()
+        };
+        let mut app = app_with(Simulation::new(params).with_mesh(mesh.clone()));
+        step(&mut app, 300);
+        positions(&mut app)[3].1.z
+    };
+
+    // Without bending gravity swings the folded triangle down into the plane of the other
+    let loose = folded_tip(0.0);
+    let stiff = folded_tip(1.0);
+    assert!(
+        stiff > loose.abs() + 4.0,
+        "bending left the fold at {}, without it {}",
+        stiff,
+        loose
+    );
+}
+
+#[test]
+fn bending_stiffness_can_be_raised_while_running() {
+    for storage in [StorageBackend::Ecs, StorageBackend::Contiguous] {
+        let params = Params {
+            storage,
+            ..params()
+        };
+        let mut app = app_with(Simulation::new(params).with_mesh(folded_mesh()));
+        step(&mut app, 1);
+        app.world.resource_mut::<Params>().bending_stiffness = 1.0;
+        step(&mut app, 299);
+
+        let tip = positions(&mut app)[3].1.z;
+        assert!(tip > 4.0, "{:?} left the fold at {}", storage, tip);
+    }
+}
+
+#[test]
+fn bending_smooths_a_poked_grid() {
+    // Poke a single node out of the plane and measure how far nodes stick out of the plane
+    // of their neighbours
+    let roughness = |bending_stiffness: f32| {
+        let mut app = app(Params {
+            bending_stiffness,
+            ..params()
+        });
+        step(&mut app, 20);
+        for (index, mut prev_pos) in app
+            .world
+            .query::<(&Index, &mut PreviousPosition)>()
+            .iter_mut(&mut app.world)
+        {
+            if index.x == 10 && index.y == 6 {
+                prev_pos.0.z -= 40.0;
+            }
+        }
+        step(&mut app, 10);
+
+        let z: HashMap<(usize, usize), f32> = positions(&mut app)
+            .into_iter()
+            .map(|(index, pos)| ((index.x, index.y), pos.z))
+            .collect();
+        (1..19)
+            .flat_map(|x| (1..11).map(move |y| (x, y)))
+            .map(|(x, y)| {
+                let neighbours = z[&(x - 1, y)] + z[&(x + 1, y)] + z[&(x, y - 1)] + z[&(x, y + 1)];
+                (z[&(x, y)] - neighbours / 4.0).abs()
+            })
+            .sum::<f32>()
+    };
+
+    let loose = roughness(0.0);
+    let stiff = roughness(1.0);
+    assert!(
+        stiff < 0.5 * loose,
+        "bending left a roughness of {}, without it {}",
+        stiff,
+        loose
+    );
+}
+
+#[test]
+fn aerodynamic_wind_acts_on_flat_grid() {
+    let calm = app(params());
+    let windy = app(Params {
+        enable_wind: true,
+        wind_model: WindModel::Aerodynamic,
+        through_wind: 500.0,
+        ..params()
+    });
+
+    let [calm, windy] = [calm, windy].map(|mut app| {
+        step(&mut app, 50);
+        positions(&mut app)
+    });
+    let moved = calm
+        .iter()
+        .zip(windy.iter())
+        .map(|((_, a), (_, b))| a.distance(*b))
+        .fold(0.0, f32::max);
+    assert!(moved > 1.0, "wind moved the cloth by only {}", moved);
+    assert!(windy.iter().any(|(_, pos)| pos.z.abs() > 1.0));
+}
+
+/// Start and displacement of every free node after one physics update with only the given
+/// wind source pushing, without gravity and edges
+fn pushed_by(source: WindSource) -> Vec<(Vec3, Vec3)> {
+    let mut app = app(Params {
+        g: 0.0,
+        enable_wind: true,
+        wind_model: WindModel::Push,
+        ..params()
+    });
+    let removed: Vec<Entity> = app
+        .world
+        .query_filtered::<Entity, Or<(With<Edge>, With<WindSource>)>>()
+        .iter(&app.world)
+        .collect();
+    for entity in removed {
+        app.world.despawn(entity);
+    }
+    app.world.spawn().insert(source);
+
+    let mut free = app
+        .world
+        .query_filtered::<(Entity, &Transform), (With<Index>, Without<Pinned>)>();
+    let start: HashMap<Entity, Vec3> = free
+        .iter(&app.world)
+        .map(|(entity, transform)| (entity, transform.translation))
+        .collect();
+    step(&mut app, 1);
+
+    free.iter(&app.world)
+        .map(|(entity, transform)| (start[&entity], transform.translation - start[&entity]))
+        .collect()
+}
+
+#[test]
+fn fan_falls_off_with_distance() {
+    // Blowing along row 5, the cloth spans x from 0 to 380
+    let mut axis: Vec<(Vec3, Vec3)> = pushed_by(WindSource::Fan {
+        origin: Vec2::new(-100.0, -100.0),
+        direction: Vec2::X,
+        force: 3000.0,
+        radius: 400.0,
+    })
+    .into_iter()
+    .filter(|(start, _)| start.y == -100.0)
+    .collect();
+    axis.sort_by(|(a, _), (b, _)| a.x.total_cmp(&b.x));
+    assert_eq!(axis.len(), 20);
+
+    for (start, pushed) in axis.iter() {
+        assert_eq!(pushed.y, 0.0);
+        if start.x + 100.0 >= 400.0 {
+            assert_eq!(pushed.x, 0.0, "node at {} is out of reach", start);
+        } else {
+            assert!(pushed.x > 0.0, "node at {} was not blown away", start);
+        }
+    }
+    for pair in axis.windows(2) {
+        let (near, far) = (pair[0].1.x, pair[1].1.x);
+        assert!(
+            far < near || far == 0.0,
+            "{} is pushed less than {}",
+            near,
+            far
+        );
+    }
+}
+
+#[test]
+fn vortex_pushes_tangentially() {
+    // Between the nodes, so none is in the center
+    let center = Vec3::new(190.0, -110.0, 0.0);
+    let pushed = pushed_by(WindSource::Vortex {
+        center: center.truncate(),
+        force: 3000.0,
+        radius: 1000.0,
+    });
+
+    assert!(!pushed.is_empty());
+    for (start, pushed) in pushed {
+        let offset = (start - center).truncate();
+        let pushed = pushed.truncate();
+        assert!(pushed.length() > 0.0);
+        assert!(
+            offset.normalize().dot(pushed.normalize()).abs() < 1e-3,
+            "node at {} is pushed along {}",
+            start,
+            pushed
+        );
+        // Counter-clockwise
+        assert!(offset.perp_dot(pushed) > 0.0);
+    }
+}
+
+#[test]
+fn default_gravity_pulls_down() {
+    let params = Params {
+        g: 10.0,
+        ..Default::default()
+    };
+    assert_eq!(params.gravity(), Vec3::new(0.0, -10.0, 0.0));
+}
+
+#[test]
+fn contiguous_storage_keeps_outside_changes() {
+    let mut app = app(Params {
+        storage: StorageBackend::Contiguous,
+        ..params()
+    });
+    step(&mut app, 20);
+
+    // Moved from outside of the physics, like a reset or the ECS backend would
+    let offset = Vec3::new(5000.0, 0.0, 0.0);
+    for (mut pos, mut prev_pos) in app
+        .world
+        .query::<(&mut Transform, &mut PreviousPosition)>()
+        .iter_mut(&mut app.world)
+    {
+        pos.translation += offset;
+        prev_pos.0 += offset;
+    }
+
+    step(&mut app, 1);
+    for (_, pos) in positions(&mut app) {
+        assert!(pos.x > 4000.0, "node snapped back to {}", pos);
+    }
+}
+
+#[test]
+fn contiguous_storage_follows_cuts() {
+    let mut app = app(Params {
+        storage: StorageBackend::Contiguous,
+        ..params()
+    });
+    step(&mut app, 20);
+
+    let lowest = |app: &mut App| {
+        positions(app)
+            .iter()
+            .map(|(_, pos)| pos.y)
+            .fold(f32::MAX, f32::min)
+    };
+    let before = lowest(&mut app);
+
+    // The cut is only reported in this frame, the storage has to drop the edges anyway
+    cut_below_row(&mut app, 5);
+    step(&mut app, 40);
+
+    let after = lowest(&mut app);
+    assert!(
+        after < before - 100.0,
+        "cut piece only fell from {} to {}",
+        before,
+        after
+    );
+}
+
+/// Weightless cloth without pins, every node moving by `kick` in the first substep and the
+/// node 10x6 by `poke` on top of it
+fn released(params: Params, kick: Vec3, poke: Vec3) -> App {
+    let mut app = app(Params { g: 0.0, ..params });
+    let pinned: Vec<Entity> = app
+        .world
+        .query_filtered::<Entity, With<Pinned>>()
+        .iter(&app.world)
+        .collect();
+    for entity in pinned {
+        app.world.entity_mut(entity).remove::<Pinned>();
+    }
+
+    for (index, mut prev_pos) in app
+        .world
+        .query::<(&Index, &mut PreviousPosition)>()
+        .iter_mut(&mut app.world)
+    {
+        prev_pos.0 -= kick;
+        if index.x == 10 && index.y == 6 {
+            prev_pos.0 -= poke;
+        }
+    }
+    app
+}
+
+/// Distance every node travelled in the last substep
+fn steps(app: &mut App) -> Vec<Vec3> {
+    app.world
+        .query::<(&Transform, &PreviousPosition)>()
+        .iter(&app.world)
+        .map(|(pos, prev_pos)| pos.translation - prev_pos.0)
+        .collect()
+}
+
+#[test]
+fn damping_models_slow_down_moving_cloth() {
+    let ticks = 10;
+    let time = ticks as f32 * params().dt;
+    let models = [
+        (
+            DampingOptions {
+                model: DampingModel::Multiplicative,
+                ..Default::default()
+            },
+            None,
+        ),
+        (
+            DampingOptions {
+                model: DampingModel::AirDrag,
+                air_drag: 2.0,
+                ..Default::default()
+            },
+            Some((-2.0 * 2.0 * time).exp()),
+        ),
+        (
+            DampingOptions {
+                model: DampingModel::Rayleigh,
+                rayleigh_alpha: 2.0,
+                rayleigh_beta: 0.01,
+                ..Default::default()
+            },
+            Some((-2.0 * 2.0 * time).exp()),
+        ),
+        // Moving as a whole does not stretch any edge
+        (
+            DampingOptions {
+                model: DampingModel::SpringAxis,
+                spring_damping: 50.0,
+                ..Default::default()
+            },
+            Some(1.0),
+        ),
+    ];
+
+    for storage in [StorageBackend::Ecs, StorageBackend::Contiguous] {
+        for (damping, energy_ratio) in models {
+            let kick = Vec3::new(1.0, 0.5, 0.0);
+            let mut app = released(
+                Params {
+                    storage,
+                    damping,
+                    ..params()
+                },
+                kick,
+                Vec3::ZERO,
+            );
+            step(&mut app, ticks);
+
+            // The multiplicative factor is applied in every substep
+            let substeps = app.world.resource::<Substeps>().total() * ticks;
+            let energy_ratio =
+                energy_ratio.unwrap_or_else(|| params().dampen_factor.powi(2 * substeps as i32));
+
+            for velocity in steps(&mut app) {
+                let ratio = velocity.length_squared() / kick.length_squared();
+                assert!(
+                    (ratio - energy_ratio).abs() < 1e-3,
+                    "{:?} with {:?} storage kept {} of the kinetic energy, expected {}",
+                    damping.model,
+                    storage,
+                    ratio,
+                    energy_ratio
+                );
+                assert!(velocity.normalize().distance(kick.normalize()) < 1e-4);
+            }
+        }
+    }
+}
+
+#[test]
+fn edge_damping_settles_stretching_but_keeps_momentum() {
+    let stretch_energy = |model: DampingModel| {
+        let mut app = released(
+            Params {
+                dampen_factor: 1.0,
+                damping: DampingOptions {
+                    model,
+                    spring_damping: 50.0,
+                    rayleigh_beta: 0.005,
+                    ..Default::default()
+                },
+                ..params()
+            },
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(10.0, 0.0, 0.0),
+        );
+        step(&mut app, 40);
+
+        let steps = steps(&mut app);
+        let mean = steps.iter().sum::<Vec3>() / steps.len() as f32;
+
+        // Relative velocity of the nodes of every edge along the edge
+        let nodes: HashMap<Entity, (Vec3, Vec3)> = app
+            .world
+            .query::<(Entity, &Transform, &PreviousPosition)>()
+            .iter(&app.world)
+            .map(|(entity, pos, prev_pos)| {
+                (entity, (pos.translation, pos.translation - prev_pos.0))
+            })
+            .collect();
+        let energy = app
+            .world
+            .query::<&Edge>()
+            .iter(&app.world)
+            .map(|edge| {
+                let (a_pos, a_step) = nodes[&edge.a];
+                let (b_pos, b_step) = nodes[&edge.b];
+                (a_step - b_step).dot((a_pos - b_pos).normalize()).powi(2)
+            })
+            .sum::<f32>();
+        (mean, energy)
+    };
+
+    // Without damping the poke keeps stretching the edges back and forth
+    let (free_mean, free_energy) = stretch_energy(DampingModel::Multiplicative);
+    for model in [DampingModel::SpringAxis, DampingModel::Rayleigh] {
+        let (mean, energy) = stretch_energy(model);
+        assert!(
+            energy < 0.5 * free_energy,
+            "{:?} left a stretching energy of {}, undamped {}",
+            model,
+            energy,
+            free_energy
+        );
+        assert!(
+            mean.distance(free_mean) < 1e-3,
+            "{:?} moves the cloth at {}, undamped {}",
+            model,
+            mean,
+            free_mean
+        );
+    }
+}
+
+#[test]
+fn rollback_substeps_have_a_lower_bound() {
+    // Everything counts as exploding, so every update rolls back
+    let mut app = app(Params {
+        stability: StabilityOptions {
+            enabled: true,
+            max_velocity: 0.001,
+            response: ExplosionResponse::Rollback,
+        },
+        ..params()
+    });
+    step(&mut app, 20);
+
+    // The fixed timestep keeps running at the configured rate, only the substeps shrink
+    assert_eq!(app.world.resource::<Params>().dt, params().dt);
+    assert_eq!(app.world.resource::<Substeps>().refinement, 16);
+}
+
+#[test]
+fn freshly_cut_fragment_falls_before_sleeping() {
+    let mut app = app(Params {
+        fragments: FragmentOptions {
+            color: false,
+            detached_action: DetachedAction::Sleep,
+        },
+        ..params()
+    });
+
+    // Let the hanging cloth come to rest
+    step(&mut app, 400);
+
+    // Cut the lower half off
+    cut_below_row(&mut app, 5);
+
+    let lowest = |app: &mut App| {
+        positions(app)
+            .iter()
+            .map(|(_, pos)| pos.y)
+            .fold(f32::MAX, f32::min)
+    };
+    let before = lowest(&mut app);
+    step(&mut app, 40);
+    let after = lowest(&mut app);
+
+    assert!(
+        after < before - 100.0,
+        "cut piece only fell from {} to {}",
+        before,
+        after
+    );
+}
+
+#[test]
+fn tethers_limit_sag_and_follow_cuts() {
+    let lowest = |app: &mut App| {
+        positions(app)
+            .iter()
+            .map(|(_, pos)| pos.y)
+            .fold(f32::MAX, f32::min)
+    };
+    // Tethers keep every node within the rest length of the path to the pinned top row
+    let limit = -((params().num_nodes_y - 1) as f32 * params().r[0]) - TOLERANCE;
+
+    let mut free = app(params());
+    step(&mut free, 200);
+    assert!(
+        lowest(&mut free) < limit,
+        "the cloth does not sag without tethers"
+    );
+
+    let mut app = app(Params {
+        enable_tethers: true,
+        ..params()
+    });
+    step(&mut app, 200);
+    assert!(
+        lowest(&mut app) >= limit,
+        "tethered cloth sags to {}",
+        lowest(&mut app)
+    );
+
+    // Cutting below row 5 detaches the lower rows from the pins
+    let rows = cut_below_row(&mut app, 5);
+    step(&mut app, 2);
+
+    let tethered_rows = |app: &App| -> Vec<usize> {
+        let tethers = app.world.resource::<Tethers>();
+        tethers
+            .tethers
+            .iter()
+            .map(|tether| rows[&tether.node])
+            .collect()
+    };
+    let tethered = tethered_rows(&app);
+    assert!(tethered.iter().any(|&row| row <= 5));
+    assert!(
+        tethered.iter().all(|&row| row <= 5),
+        "detached nodes are still tethered"
+    );
+
+    // Without any pins nothing is tethered
+    let pinned: Vec<Entity> = app
+        .world
+        .query_filtered::<Entity, With<Pinned>>()
+        .iter(&app.world)
+        .collect();
+    for entity in pinned {
+        app.world.entity_mut(entity).remove::<Pinned>();
+    }
+    step(&mut app, 2);
+    assert!(tethered_rows(&app).is_empty());
+}
+
+#[test]
+fn fragments_follow_cuts_within_a_frame() {
+    let mut app = app(Params {
+        bounds: WorldBounds {
+            floor: Some(-400.0),
+            friction: 1.0,
+            ..Default::default()
+        },
+        fragments: FragmentOptions {
+            color: false,
+            detached_action: DetachedAction::Sleep,
+        },
+        ..params()
+    });
+    let rows = cut_below_row(&mut app, 5);
+    step(&mut app, 1);
+
+    // Cut the fallen piece once more, the physics updates below run like the catch-up
+    // updates of a single frame, without the fragments being recalculated after it
+    let cut = app
+        .world
+        .query::<(Entity, &Edge)>()
+        .iter(&app.world)
+        .find(|(_, edge)| rows[&edge.a] > 5 && rows[&edge.b] > 5)
+        .map(|(entity, _)| entity)
+        .unwrap();
+    app.world.despawn(cut);
+
+    for _ in 0..400 {
+        app.schedule
+            .get_stage_mut::<SystemStage>(&PhysicsStage)
+            .unwrap()
+            .run(&mut app.world);
+    }
+
+    // The piece came to rest on the floor and was put to sleep
+    let lower_rows = |app: &mut App| -> Vec<Vec3> {
+        positions(app)
+            .into_iter()
+            .filter(|(index, _)| index.y > 5)
+            .map(|(_, pos)| pos)
+            .collect()
+    };
+    let before = lower_rows(&mut app);
+    assert!(before.iter().all(|pos| pos.y < -300.0));
+    step(&mut app, 10);
+    assert_eq!(before, lower_rows(&mut app));
+}